@@ -0,0 +1,6 @@
+-- Window the ad insights are judged over, per account.
+-- Preset (today, yesterday, last_3d, last_7d, last_30d, lifetime)
+-- or a custom range written as YYYY-MM-DD..YYYY-MM-DD.
+-- NULL falls back to last_30d, Facebook's default.
+ALTER TABLE facebook_accounts
+    ADD COLUMN IF NOT EXISTS insights_window TEXT;
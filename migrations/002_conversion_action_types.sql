@@ -0,0 +1,6 @@
+-- Action types counted as conversions when computing cost per action, per account.
+-- e.g. '{lead}', '{offsite_conversion.fb_pixel_purchase}',
+--      '{onsite_conversion.messaging_conversation_started_7d}'
+-- NULL or empty falls back to offsite_conversion.fb_pixel_custom.
+ALTER TABLE facebook_accounts
+    ADD COLUMN IF NOT EXISTS conversion_action_types TEXT[];
@@ -0,0 +1,5 @@
+-- Alert the account's Telegram chat when remaining funds (prepaid balance or
+-- spend cap headroom) drop below this amount, in the account currency.
+-- NULL disables the alert.
+ALTER TABLE facebook_accounts
+    ADD COLUMN IF NOT EXISTS low_funds_threshold DOUBLE PRECISION;
@@ -0,0 +1,9 @@
+-- Access token health, recorded from /debug_token.
+ALTER TABLE facebook_accounts
+    ADD COLUMN IF NOT EXISTS token_is_valid BOOLEAN,
+    ADD COLUMN IF NOT EXISTS token_type TEXT,
+    ADD COLUMN IF NOT EXISTS token_scopes TEXT[],
+    ADD COLUMN IF NOT EXISTS token_expires_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS token_data_access_expires_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS token_error TEXT,
+    ADD COLUMN IF NOT EXISTS token_checked_at TIMESTAMPTZ;
@@ -0,0 +1,5 @@
+-- Level threshold rules act on: 'ad' (default), 'adset' or 'campaign'.
+-- Above ad level the rule is judged on the ad set's or campaign's own insights
+-- and pauses or resumes the whole ad set or campaign.
+ALTER TABLE ad_thresholds
+    ADD COLUMN IF NOT EXISTS control_level TEXT NOT NULL DEFAULT 'ad';
@@ -0,0 +1,27 @@
+-- Automatic budget scaling for ad sets that keep beating a target cost per
+-- action. Accounts without an enabled row are never scaled.
+CREATE TABLE IF NOT EXISTS budget_scaling_rules (
+    account_id INTEGER PRIMARY KEY REFERENCES facebook_accounts(id) ON DELETE CASCADE,
+    is_enabled BOOLEAN NOT NULL DEFAULT true,
+    target_cost_per_action DOUBLE PRECISION NOT NULL,
+    window_days INTEGER NOT NULL DEFAULT 3 CHECK (window_days > 0),
+    increase_pct DOUBLE PRECISION NOT NULL DEFAULT 20 CHECK (increase_pct > 0),
+    max_daily_increase_pct DOUBLE PRECISION NOT NULL DEFAULT 20 CHECK (max_daily_increase_pct > 0),
+    max_daily_budget DOUBLE PRECISION
+);
+
+-- Every raise the worker applied, in account currency.
+CREATE TABLE IF NOT EXISTS budget_changes (
+    id SERIAL PRIMARY KEY,
+    account_id INTEGER NOT NULL REFERENCES facebook_accounts(id) ON DELETE CASCADE,
+    adset_id TEXT NOT NULL,
+    adset_name TEXT NOT NULL,
+    old_budget DOUBLE PRECISION NOT NULL,
+    new_budget DOUBLE PRECISION NOT NULL,
+    currency TEXT NOT NULL,
+    cost_per_action DOUBLE PRECISION NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS budget_changes_adset_idx
+    ON budget_changes (account_id, adset_id, changed_at);
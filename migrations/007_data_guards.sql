@@ -0,0 +1,8 @@
+-- Minimum data an ad, ad set or campaign needs before a threshold rule may
+-- pause or resume it. Below min_conversions only spend decides: spending more
+-- than min_conversions conversions' worth at max_cost_per_action pauses it.
+ALTER TABLE ad_thresholds
+    ADD COLUMN IF NOT EXISTS min_spend DOUBLE PRECISION NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS min_impressions BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS min_age_hours BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS min_conversions BIGINT NOT NULL DEFAULT 1;
@@ -0,0 +1,20 @@
+-- Separate resume threshold, so an object near the limit is not paused and
+-- resumed in turns. NULL resumes below max_cost_per_action as before.
+ALTER TABLE ad_thresholds
+    ADD COLUMN IF NOT EXISTS resume_cost_per_action DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS cooldown_hours BIGINT NOT NULL DEFAULT 24;
+
+-- Every pause or resume the worker applied; the latest one per object starts
+-- its cooldown.
+CREATE TABLE IF NOT EXISTS status_changes (
+    id SERIAL PRIMARY KEY,
+    account_id INTEGER NOT NULL REFERENCES facebook_accounts(id) ON DELETE CASCADE,
+    level TEXT NOT NULL,
+    object_id TEXT NOT NULL,
+    object_name TEXT NOT NULL,
+    status TEXT NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS status_changes_object_idx
+    ON status_changes (account_id, object_id, changed_at);
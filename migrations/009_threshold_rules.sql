@@ -0,0 +1,34 @@
+-- Threshold rules scoped global -> account -> campaign -> ad set. A rule
+-- without account_id is global; campaign_id or adset_id narrow it further.
+-- NULL settings are inherited from the next broader scope.
+CREATE TABLE IF NOT EXISTS threshold_rules (
+    id SERIAL PRIMARY KEY,
+    account_id INTEGER REFERENCES facebook_accounts(id) ON DELETE CASCADE,
+    campaign_id TEXT,
+    adset_id TEXT,
+    max_cost_per_action DOUBLE PRECISION,
+    resume_cost_per_action DOUBLE PRECISION,
+    cooldown_hours BIGINT,
+    control_level TEXT,
+    min_spend DOUBLE PRECISION,
+    min_impressions BIGINT,
+    min_age_hours BIGINT,
+    min_conversions BIGINT,
+    CHECK (account_id IS NOT NULL OR (campaign_id IS NULL AND adset_id IS NULL))
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS threshold_rules_scope_idx
+    ON threshold_rules (COALESCE(account_id, 0), COALESCE(campaign_id, ''), COALESCE(adset_id, ''));
+
+-- Carry the single ad_thresholds row over as the global rule. ad_thresholds
+-- is no longer read and can be dropped once this has run.
+INSERT INTO threshold_rules (
+    max_cost_per_action, resume_cost_per_action, cooldown_hours, control_level,
+    min_spend, min_impressions, min_age_hours, min_conversions
+)
+SELECT
+    max_cost_per_action, resume_cost_per_action, cooldown_hours, control_level,
+    min_spend, min_impressions, min_age_hours, min_conversions
+FROM ad_thresholds
+LIMIT 1
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,4 @@
+-- Start of the last monitoring run, so the scheduler can tell after a restart
+-- which accounts missed their interval.
+ALTER TABLE facebook_accounts
+    ADD COLUMN IF NOT EXISTS last_run_at TIMESTAMPTZ;
@@ -0,0 +1,7 @@
+-- Daily insights are requested with a page size covering the whole window, so
+-- `window_days` needs no upper bound. Restores the original check on
+-- databases that applied an earlier 90 day cap.
+ALTER TABLE budget_scaling_rules
+    DROP CONSTRAINT IF EXISTS budget_scaling_rules_window_days_check;
+ALTER TABLE budget_scaling_rules
+    ADD CONSTRAINT budget_scaling_rules_window_days_check CHECK (window_days > 0);
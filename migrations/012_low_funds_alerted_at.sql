@@ -0,0 +1,5 @@
+-- When the low funds alert was sent. Set while funds stay below
+-- `low_funds_threshold` so the alert goes out once per drop, cleared once
+-- they recover.
+ALTER TABLE facebook_accounts
+    ADD COLUMN IF NOT EXISTS low_funds_alerted_at TIMESTAMPTZ;
@@ -1,19 +1,262 @@
 use dotenv::dotenv;
 use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 use thiserror::Error;
 use url::Url;
+use crate::secret::SecretString;
+use crate::constants::{
+    FB_DEFAULT_PAGE_SIZE,
+    FB_DEFAULT_MAX_ITEMS,
+    FB_RATE_LIMIT_SLOWDOWN_PCT,
+    FB_RATE_LIMIT_PAUSE_PCT,
+    FB_RATE_LIMIT_MAX_DELAY_MS,
+    FB_RATE_LIMIT_PAUSE_SECS,
+    RETRY_MAX_ATTEMPTS,
+    RETRY_BASE_DELAY_MS,
+    RETRY_MAX_DELAY_MS,
+    RETRY_JITTER,
+    TOKEN_CHECK_INTERVAL_HOURS,
+    TOKEN_EXPIRY_WARNING_DAYS,
+    TOKEN_REFRESH_BEFORE_DAYS,
+    WORKER_INTERVAL_SECS,
+    SCHEDULER_JITTER,
+    WORKER_MAX_CONCURRENT_RUNS,
+    WORKER_RUN_TIMEOUT_SECS,
+    ACCOUNT_RELOAD_SECS,
+    DB_POOL_MAX_SIZE,
+    DB_POOL_WAIT_TIMEOUT_SECS,
+    DB_CONNECT_TIMEOUT_SECS,
+    DB_RECYCLE_TIMEOUT_SECS,
+};
 
 #[derive(Debug)]
 pub struct Config {
     pub database_url: String,
+    pub database_pool: DatabasePoolConfig,
+    pub database_tls: DatabaseTlsConfig,
+    pub retry: RetryConfig,
+    pub graph: GraphApiConfig,
+    pub token: TokenCheckConfig,
+    pub scheduler: SchedulerConfig,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct DatabasePoolConfig {
+    pub max_size: usize,           // Connections open at most
+    pub wait_timeout_secs: u64,    // Waiting for a free connection
+    pub connect_timeout_secs: u64, // Opening a new connection
+    pub recycle_timeout_secs: u64, // Health check before a connection is reused
+}
+
+impl Default for DatabasePoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: DB_POOL_MAX_SIZE,
+            wait_timeout_secs: DB_POOL_WAIT_TIMEOUT_SECS,
+            connect_timeout_secs: DB_CONNECT_TIMEOUT_SECS,
+            recycle_timeout_secs: DB_RECYCLE_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// TLS settings for the Postgres connection, read from the `sslmode`,
+/// `sslrootcert`, `sslcert` and `sslkey` URL parameters or the `DB_SSL*`
+/// environment variables.
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseTlsConfig {
+    pub ssl_mode: SslMode,
+    pub root_cert: Option<PathBuf>,   // PEM bundle trusted for the server certificate
+    pub client_cert: Option<PathBuf>, // PEM certificate chain presented to the server
+    pub client_key: Option<PathBuf>,
+}
+
+/// How the server certificate is checked, with the same meaning as libpq's `sslmode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SslMode {
+    Disable,    // Plain connection
+    Prefer,     // TLS if the server offers it, certificate not checked
+    Require,    // TLS, certificate not checked
+    VerifyCa,   // TLS, certificate signed by a trusted CA
+    #[default]
+    VerifyFull, // TLS, trusted certificate issued for the host name
+}
+
+impl SslMode {
+    pub fn verifies_certificate(&self) -> bool {
+        matches!(self, Self::VerifyCa | Self::VerifyFull)
+    }
+}
+
+impl FromStr for SslMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "disable" => Ok(Self::Disable),
+            "prefer" => Ok(Self::Prefer),
+            "require" => Ok(Self::Require),
+            "verify-ca" | "verify_ca" => Ok(Self::VerifyCa),
+            "verify-full" | "verify_full" => Ok(Self::VerifyFull),
+            other => Err(format!("unknown sslmode: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for SslMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Disable => "disable",
+            Self::Prefer => "prefer",
+            Self::Require => "require",
+            Self::VerifyCa => "verify-ca",
+            Self::VerifyFull => "verify-full",
+        })
+    }
+}
+
+/// Settings shared by every `FacebookAPI` client the worker creates.
+#[derive(Debug, Clone)]
+pub struct GraphApiConfig {
+    pub pagination: PaginationConfig,
+    pub rate_limit: RateLimitConfig,
+    pub retry: RetryConfig,
+    pub app: Option<AppCredentials>,
+}
+
+/// Meta app credentials, required when the app has "Require App Secret" enabled.
+#[derive(Debug, Clone)]
+pub struct AppCredentials {
+    pub app_id: String,
+    pub app_secret: SecretString,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PaginationConfig {
+    pub page_size: u32,   // `limit` sent with each page request
+    pub max_items: usize, // Stop following cursors after this many items
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            page_size: FB_DEFAULT_PAGE_SIZE,
+            max_items: FB_DEFAULT_MAX_ITEMS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    pub slowdown_pct: f64, // Start spacing out requests above this usage
+    pub pause_pct: f64,    // Stop sending requests above this usage
+    pub max_delay_ms: u64, // Delay just below `pause_pct`
+    pub pause_secs: u64,   // Minimum back-off when paused or throttled
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            slowdown_pct: FB_RATE_LIMIT_SLOWDOWN_PCT,
+            pause_pct: FB_RATE_LIMIT_PAUSE_PCT,
+            max_delay_ms: FB_RATE_LIMIT_MAX_DELAY_MS,
+            pause_secs: FB_RATE_LIMIT_PAUSE_SECS,
+        }
+    }
+}
+
+/// Retry policy shared by the Facebook and Telegram clients.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+    pub max_attempts: u32,  // Including the first attempt
+    pub base_delay_ms: u64, // Doubled after every failed attempt
+    pub max_delay_ms: u64,
+    pub jitter: f64,        // Random spread of each delay, 0.0..=1.0
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: RETRY_MAX_ATTEMPTS,
+            base_delay_ms: RETRY_BASE_DELAY_MS,
+            max_delay_ms: RETRY_MAX_DELAY_MS,
+            jitter: RETRY_JITTER,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TokenCheckConfig {
+    pub check_interval_hours: i64, // How often a valid token is re-inspected
+    pub warning_days: i64,         // Warn this many days before the token expires
+    pub refresh_before_days: i64,  // Exchange user tokens expiring within this many days
+}
+
+impl Default for TokenCheckConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_hours: TOKEN_CHECK_INTERVAL_HOURS,
+            warning_days: TOKEN_EXPIRY_WARNING_DAYS,
+            refresh_before_days: TOKEN_REFRESH_BEFORE_DAYS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SchedulerConfig {
+    pub default_interval_secs: u64, // For accounts whose interval is not set
+    pub jitter: f64,                // Random spread of each run, as a fraction of the interval
+    pub catch_up: CatchUp,
+    pub max_concurrent_runs: usize, // Accounts processed at the same time
+    pub run_timeout_secs: u64,      // A run taking longer is abandoned and reported
+    pub reload_secs: u64,           // Re-read accounts and their settings this often
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            default_interval_secs: WORKER_INTERVAL_SECS,
+            jitter: SCHEDULER_JITTER,
+            catch_up: CatchUp::default(),
+            max_concurrent_runs: WORKER_MAX_CONCURRENT_RUNS,
+            run_timeout_secs: WORKER_RUN_TIMEOUT_SECS,
+            reload_secs: ACCOUNT_RELOAD_SECS,
+        }
+    }
+}
+
+/// What to do about runs missed while the worker was down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CatchUp {
+    #[default]
+    RunOnce, // Run overdue accounts once right away
+    Skip,    // Wait for the next regular slot
+}
+
+impl FromStr for CatchUp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "run_once" | "once" => Ok(Self::RunOnce),
+            "skip" => Ok(Self::Skip),
+            other => Err(format!("unknown catch-up rule: {}", other)),
+        }
+    }
+}
+
+// libpq connection parameters handled by `DatabaseTlsConfig`
+const SSL_URL_PARAMS: &[&str] = &["sslmode", "sslrootcert", "sslcert", "sslkey"];
+
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("Environment variable not found: {0}")]
     MissingEnv(String),
     #[error("Invalid database URL: {0}")]
     InvalidDatabaseUrl(String),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidValue(String, String),
 }
 
 impl Config {
@@ -24,9 +267,160 @@ impl Config {
             .map_err(|_| ConfigError::MissingEnv("DATABASE_URL".to_string()))?;
 
         // Validate the URL format
-        Url::parse(&database_url)
+        let mut url = Url::parse(&database_url)
             .map_err(|e| ConfigError::InvalidDatabaseUrl(e.to_string()))?;
 
-        Ok(Self { database_url })
+        // tokio-postgres rejects the libpq TLS parameters it doesn't know, so
+        // they are taken out of the URL and applied when connecting
+        let (ssl_params, params): (Vec<_>, Vec<_>) = url
+            .query_pairs()
+            .into_owned()
+            .partition(|(key, _)| SSL_URL_PARAMS.contains(&key.as_str()));
+        if params.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(params);
+        }
+        let database_url = url.to_string();
+
+        // Environment variables take precedence over the URL
+        let ssl_setting = |name: &str, param: &str| {
+            env::var(name).ok().or_else(|| {
+                ssl_params
+                    .iter()
+                    .find(|(key, _)| key == param)
+                    .map(|(_, value)| value.clone())
+            })
+        };
+
+        let mut ssl_mode = match ssl_setting("DB_SSLMODE", "sslmode") {
+            Some(value) => value
+                .parse()
+                .map_err(|_| ConfigError::InvalidValue("DB_SSLMODE".to_string(), value))?,
+            None => SslMode::default(),
+        };
+        let root_cert = ssl_setting("DB_SSL_ROOT_CERT", "sslrootcert").map(PathBuf::from);
+
+        // Like libpq, `require` with a root certificate checks the CA
+        if ssl_mode == SslMode::Require && root_cert.is_some() {
+            ssl_mode = SslMode::VerifyCa;
+        }
+
+        if !ssl_mode.verifies_certificate() && !env_or("DB_SSL_ALLOW_INSECURE", false)? {
+            return Err(ConfigError::InvalidValue(
+                "DB_SSLMODE".to_string(),
+                format!(
+                    "{} does not verify the server certificate; set DB_SSL_ALLOW_INSECURE=true for local development",
+                    ssl_mode
+                ),
+            ));
+        }
+
+        let database_tls = DatabaseTlsConfig {
+            ssl_mode,
+            root_cert,
+            client_cert: ssl_setting("DB_SSL_CERT", "sslcert").map(PathBuf::from),
+            client_key: ssl_setting("DB_SSL_KEY", "sslkey").map(PathBuf::from),
+        };
+        match (&database_tls.client_cert, &database_tls.client_key) {
+            (Some(_), None) => return Err(ConfigError::MissingEnv("DB_SSL_KEY".to_string())),
+            (None, Some(_)) => return Err(ConfigError::MissingEnv("DB_SSL_CERT".to_string())),
+            _ => {}
+        }
+
+        let defaults = DatabasePoolConfig::default();
+        let database_pool = DatabasePoolConfig {
+            max_size: env_or("DB_POOL_MAX_SIZE", defaults.max_size)?.max(1),
+            wait_timeout_secs: env_or("DB_POOL_WAIT_TIMEOUT_SECS", defaults.wait_timeout_secs)?,
+            connect_timeout_secs: env_or("DB_CONNECT_TIMEOUT_SECS", defaults.connect_timeout_secs)?,
+            recycle_timeout_secs: env_or("DB_RECYCLE_TIMEOUT_SECS", defaults.recycle_timeout_secs)?,
+        };
+
+        let defaults = PaginationConfig::default();
+        let pagination = PaginationConfig {
+            page_size: env_or("FB_PAGE_SIZE", defaults.page_size)?,
+            max_items: env_or("FB_MAX_ITEMS", defaults.max_items)?,
+        };
+
+        let defaults = RateLimitConfig::default();
+        let rate_limit = RateLimitConfig {
+            slowdown_pct: env_or("FB_RATE_LIMIT_SLOWDOWN_PCT", defaults.slowdown_pct)?,
+            pause_pct: env_or("FB_RATE_LIMIT_PAUSE_PCT", defaults.pause_pct)?,
+            max_delay_ms: env_or("FB_RATE_LIMIT_MAX_DELAY_MS", defaults.max_delay_ms)?,
+            pause_secs: env_or("FB_RATE_LIMIT_PAUSE_SECS", defaults.pause_secs)?,
+        };
+        if rate_limit.slowdown_pct >= rate_limit.pause_pct {
+            return Err(ConfigError::InvalidValue(
+                "FB_RATE_LIMIT_SLOWDOWN_PCT".to_string(),
+                format!("must be below FB_RATE_LIMIT_PAUSE_PCT ({})", rate_limit.pause_pct),
+            ));
+        }
+
+        let defaults = RetryConfig::default();
+        let retry = RetryConfig {
+            max_attempts: env_or("RETRY_MAX_ATTEMPTS", defaults.max_attempts)?.max(1),
+            base_delay_ms: env_or("RETRY_BASE_DELAY_MS", defaults.base_delay_ms)?,
+            max_delay_ms: env_or("RETRY_MAX_DELAY_MS", defaults.max_delay_ms)?,
+            jitter: env_or("RETRY_JITTER", defaults.jitter)?,
+        };
+        if !(0.0..=1.0).contains(&retry.jitter) {
+            return Err(ConfigError::InvalidValue(
+                "RETRY_JITTER".to_string(),
+                "must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+
+        let app = match (env::var("FB_APP_ID"), env::var("FB_APP_SECRET")) {
+            (Ok(app_id), Ok(app_secret)) => Some(AppCredentials {
+                app_id,
+                app_secret: SecretString::new(app_secret),
+            }),
+            (Err(_), Err(_)) => None,
+            (Ok(_), Err(_)) => return Err(ConfigError::MissingEnv("FB_APP_SECRET".to_string())),
+            (Err(_), Ok(_)) => return Err(ConfigError::MissingEnv("FB_APP_ID".to_string())),
+        };
+
+        let defaults = TokenCheckConfig::default();
+        let token = TokenCheckConfig {
+            check_interval_hours: env_or("TOKEN_CHECK_INTERVAL_HOURS", defaults.check_interval_hours)?,
+            warning_days: env_or("TOKEN_EXPIRY_WARNING_DAYS", defaults.warning_days)?,
+            refresh_before_days: env_or("TOKEN_REFRESH_BEFORE_DAYS", defaults.refresh_before_days)?,
+        };
+
+        let defaults = SchedulerConfig::default();
+        let scheduler = SchedulerConfig {
+            default_interval_secs: defaults.default_interval_secs,
+            jitter: env_or("SCHEDULER_JITTER", defaults.jitter)?,
+            catch_up: env_or("SCHEDULER_CATCH_UP", defaults.catch_up)?,
+            max_concurrent_runs: env_or("WORKER_MAX_CONCURRENT_RUNS", defaults.max_concurrent_runs)?.max(1),
+            run_timeout_secs: env_or("WORKER_RUN_TIMEOUT_SECS", defaults.run_timeout_secs)?,
+            reload_secs: env_or("ACCOUNT_RELOAD_SECS", defaults.reload_secs)?.max(1),
+        };
+        if !(0.0..=0.5).contains(&scheduler.jitter) {
+            return Err(ConfigError::InvalidValue(
+                "SCHEDULER_JITTER".to_string(),
+                "must be between 0.0 and 0.5".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            database_url,
+            database_pool,
+            database_tls,
+            retry,
+            graph: GraphApiConfig { pagination, rate_limit, retry, app },
+            token,
+            scheduler,
+        })
     }
-} 
\ No newline at end of file
+}
+
+/// Reads an optional environment variable, falling back to `default` when unset.
+fn env_or<T: FromStr>(name: &str, default: T) -> Result<T, ConfigError> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| ConfigError::InvalidValue(name.to_string(), value)),
+        Err(_) => Ok(default),
+    }
+}
@@ -9,9 +9,50 @@ pub const TELEGRAM_BASE_URL: &str = "https://api.telegram.org";
 pub const CONTENT_TYPE_HEADER: &str = "Content-Type";
 pub const CONTENT_TYPE_JSON: &str = "application/json";
 
+// Database Pool
+pub const DB_POOL_MAX_SIZE: usize = 8;
+pub const DB_POOL_WAIT_TIMEOUT_SECS: u64 = 10;
+pub const DB_CONNECT_TIMEOUT_SECS: u64 = 5;
+pub const DB_RECYCLE_TIMEOUT_SECS: u64 = 5;
+
 // Worker Settings
-pub const WORKER_INTERVAL_SECS: u64 = 1800; // 30 minutes
+pub const WORKER_INTERVAL_SECS: u64 = 1800; // 30 minutes, for accounts without an interval
+pub const SCHEDULER_JITTER: f64 = 0.1;      // Fraction of an account's interval
+pub const WORKER_MAX_CONCURRENT_RUNS: usize = 4;
+pub const WORKER_RUN_TIMEOUT_SECS: u64 = 600; // 10 minutes per account run
+pub const ACCOUNT_RELOAD_SECS: u64 = 60;       // How often accounts are re-read from the database
+
+// Threshold Rules
+pub const THRESHOLD_COOLDOWN_HOURS: i64 = 24; // When no rule sets `cooldown_hours`
 
 // Facebook API Fields
-pub const FB_AD_FIELDS: &str = "id,name,status,effective_status,insights.fields(impressions,reach,clicks,spend,cost_per_action_type,actions)";
-pub const FB_ACCOUNT_FIELDS: &str = "balance,name,id,account_status,currency"; 
\ No newline at end of file
+pub const FB_AD_FIELDS: &str = "id,name,status,effective_status,created_time,adset_id,campaign_id";
+pub const FB_ADSET_FIELDS: &str = "id,name,status,effective_status,created_time,campaign_id,daily_budget,lifetime_budget,bid_strategy,optimization_goal";
+pub const FB_CAMPAIGN_FIELDS: &str = "id,name,status,effective_status,created_time,objective,daily_budget,lifetime_budget,bid_strategy";
+pub const FB_AD_INSIGHTS_FIELDS: &str = "impressions,reach,clicks,spend,cost_per_action_type,actions,date_start,date_stop";
+pub const FB_DEFAULT_CONVERSION_ACTION_TYPE: &str = "offsite_conversion.fb_pixel_custom";
+pub const FB_ACCOUNT_FIELDS: &str = "balance,name,id,account_status,currency,timezone_name,timezone_offset_hours_utc,amount_spent,spend_cap,is_prepay_account,funding_source_details";
+
+// Access Token Health
+pub const TOKEN_CHECK_INTERVAL_HOURS: i64 = 24;
+pub const TOKEN_EXPIRY_WARNING_DAYS: i64 = 7;
+pub const TOKEN_REFRESH_BEFORE_DAYS: i64 = 14;
+
+// Facebook API Batch Requests
+pub const FB_BATCH_MAX_REQUESTS: usize = 50;
+
+// Facebook API Pagination
+pub const FB_DEFAULT_PAGE_SIZE: u32 = 100;
+pub const FB_DEFAULT_MAX_ITEMS: usize = 5000;
+
+// Facebook API Rate Limiting
+pub const FB_RATE_LIMIT_SLOWDOWN_PCT: f64 = 75.0;
+pub const FB_RATE_LIMIT_PAUSE_PCT: f64 = 95.0;
+pub const FB_RATE_LIMIT_MAX_DELAY_MS: u64 = 10_000;
+pub const FB_RATE_LIMIT_PAUSE_SECS: u64 = 300;
+
+// Retry Settings (Facebook and Telegram)
+pub const RETRY_MAX_ATTEMPTS: u32 = 3;
+pub const RETRY_BASE_DELAY_MS: u64 = 1000;
+pub const RETRY_MAX_DELAY_MS: u64 = 30_000;
+pub const RETRY_JITTER: f64 = 0.2;
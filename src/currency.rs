@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// ISO-4217 currency details needed to read and display Graph API amounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Currency {
+    pub code: &'static str,
+    pub symbol: &'static str,
+    pub minor_units: u32, // Decimal places per ISO-4217
+    pub graph_offset: u32, // Divisor Facebook applies to amounts on ad account fields
+}
+
+// Code, symbol, ISO-4217 minor units
+const CURRENCIES: &[(&str, &str, u32)] = &[
+    ("AED", "AED ", 2), ("ARS", "AR$", 2), ("AUD", "A$", 2), ("BDT", "৳", 2),
+    ("BHD", "BHD ", 3), ("BRL", "R$", 2), ("CAD", "CA$", 2), ("CHF", "CHF ", 2),
+    ("CLP", "CLP$", 0), ("CNY", "CN¥", 2), ("COP", "COL$", 2), ("CRC", "₡", 2),
+    ("CZK", "Kč ", 2), ("DKK", "kr ", 2), ("EGP", "E£", 2), ("EUR", "€", 2),
+    ("GBP", "£", 2), ("HKD", "HK$", 2), ("HUF", "Ft ", 2), ("IDR", "Rp", 2),
+    ("ILS", "₪", 2), ("INR", "₹", 2), ("ISK", "kr ", 0), ("JOD", "JOD ", 3),
+    ("JPY", "¥", 0), ("KRW", "₩", 0), ("KWD", "KWD ", 3), ("KHR", "៛", 2),
+    ("LAK", "₭", 2), ("MMK", "K ", 2), ("MXN", "MX$", 2), ("MYR", "RM", 2),
+    ("NGN", "₦", 2), ("NOK", "kr ", 2), ("NZD", "NZ$", 2), ("OMR", "OMR ", 3),
+    ("PHP", "₱", 2), ("PKR", "₨", 2), ("PLN", "zł ", 2), ("PYG", "₲", 0),
+    ("QAR", "QAR ", 2), ("RUB", "₽", 2), ("SAR", "SAR ", 2), ("SEK", "kr ", 2),
+    ("SGD", "S$", 2), ("THB", "฿", 2), ("TND", "TND ", 3), ("TRY", "₺", 2),
+    ("TWD", "NT$", 2), ("UAH", "₴", 2), ("USD", "$", 2), ("VND", "₫", 0),
+    ("ZAR", "R", 2),
+];
+
+// Currencies Facebook reports without an offset even where ISO-4217 has minor units
+const GRAPH_NO_OFFSET: &[&str] = &[
+    "CLP", "COP", "CRC", "HUF", "ISK", "IDR", "JPY", "KRW", "PYG", "TWD", "VND",
+];
+
+impl Currency {
+    /// Looks up `code`; unknown currencies are shown by code with two decimals.
+    pub fn from_code(code: &str) -> Self {
+        let (code, symbol, minor_units) = CURRENCIES
+            .iter()
+            .find(|(c, _, _)| c.eq_ignore_ascii_case(code))
+            .copied()
+            .unwrap_or(("", "", 2));
+
+        Self {
+            code,
+            symbol,
+            minor_units,
+            graph_offset: if GRAPH_NO_OFFSET.contains(&code) { 1 } else { 100 },
+        }
+    }
+}
+
+/// An amount in major units (e.g. baht, dollars) together with its currency.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Money {
+    pub amount: f64,
+    pub currency: String,
+}
+
+impl Money {
+    pub fn new(amount: f64, currency: &str) -> Self {
+        Self {
+            amount,
+            currency: currency.to_uppercase(),
+        }
+    }
+
+    /// Reads an ad account amount field (`balance`, `spend_cap`, ...), which
+    /// Facebook returns as a string in the currency's offset units.
+    pub fn from_graph(value: &Value, currency: &str) -> Option<Self> {
+        let raw = match value {
+            Value::String(s) => s.parse::<f64>().ok()?,
+            other => other.as_f64()?,
+        };
+        let offset = Currency::from_code(currency).graph_offset;
+        Some(Self::new(raw / offset as f64, currency))
+    }
+
+    /// The amount in the currency's offset units, as Graph API expects for budgets.
+    pub fn to_graph(&self) -> String {
+        let offset = Currency::from_code(&self.currency).graph_offset;
+        format!("{:.0}", (self.amount * offset as f64).round())
+    }
+
+    /// Rounds down to the smallest amount Graph API can represent.
+    pub fn floor_to_graph(&self) -> Self {
+        let offset = Currency::from_code(&self.currency).graph_offset as f64;
+        Self::new((self.amount * offset).floor() / offset, &self.currency)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let currency = Currency::from_code(&self.currency);
+        let decimals = currency.minor_units as usize;
+        let formatted = group_thousands(&format!("{:.*}", decimals, self.amount.abs()));
+        let sign = if self.amount < 0.0 { "-" } else { "" };
+
+        if currency.symbol.is_empty() {
+            write!(f, "{}{} {}", sign, formatted, self.currency)
+        } else {
+            write!(f, "{}{}{}", sign, currency.symbol, formatted)
+        }
+    }
+}
+
+fn group_thousands(number: &str) -> String {
+    let (integer, fraction) = match number.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (number, None),
+    };
+
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+
+    match fraction {
+        Some(fraction) => format!("{}.{}", grouped, fraction),
+        None => grouped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn graph_offset_follows_facebook_not_iso() {
+        assert_eq!(Currency::from_code("THB").graph_offset, 100);
+        assert_eq!(Currency::from_code("USD").graph_offset, 100);
+        assert_eq!(Currency::from_code("JPY").graph_offset, 1);
+        assert_eq!(Currency::from_code("VND").graph_offset, 1);
+        // Minor units in ISO-4217, but no offset on Facebook
+        assert_eq!(Currency::from_code("IDR").minor_units, 2);
+        assert_eq!(Currency::from_code("IDR").graph_offset, 1);
+    }
+
+    #[test]
+    fn every_no_offset_currency_is_known() {
+        for code in GRAPH_NO_OFFSET {
+            assert_eq!(Currency::from_code(code).code, *code);
+        }
+    }
+
+    #[test]
+    fn unknown_currency_uses_offset_and_two_decimals() {
+        let currency = Currency::from_code("XYZ");
+        assert_eq!(currency.graph_offset, 100);
+        assert_eq!(currency.minor_units, 2);
+        assert_eq!(Money::new(1234.5, "xyz").to_string(), "1,234.50 XYZ");
+    }
+
+    #[test]
+    fn thb_round_trips_through_offset_units() {
+        let money = Money::from_graph(&json!("150050"), "THB").unwrap();
+        assert_eq!(money, Money::new(1500.5, "THB"));
+        assert_eq!(money.to_graph(), "150050");
+        assert_eq!(money.to_string(), "฿1,500.50");
+    }
+
+    #[test]
+    fn jpy_round_trips_without_offset() {
+        let money = Money::from_graph(&json!("1500"), "JPY").unwrap();
+        assert_eq!(money, Money::new(1500.0, "JPY"));
+        assert_eq!(money.to_graph(), "1500");
+        assert_eq!(money.to_string(), "¥1,500");
+    }
+
+    #[test]
+    fn vnd_round_trips_without_offset() {
+        let money = Money::from_graph(&json!(2500000), "vnd").unwrap();
+        assert_eq!(money, Money::new(2_500_000.0, "VND"));
+        assert_eq!(money.to_graph(), "2500000");
+        assert_eq!(money.to_string(), "₫2,500,000");
+    }
+
+    #[test]
+    fn from_graph_rejects_non_numbers() {
+        assert_eq!(Money::from_graph(&json!("n/a"), "THB"), None);
+        assert_eq!(Money::from_graph(&json!(null), "THB"), None);
+    }
+
+    #[test]
+    fn floor_to_graph_never_rounds_up() {
+        assert_eq!(Money::new(12.349, "THB").floor_to_graph(), Money::new(12.34, "THB"));
+        assert_eq!(Money::new(1234.9, "JPY").floor_to_graph(), Money::new(1234.0, "JPY"));
+        assert_eq!(Money::new(99_999.99, "VND").floor_to_graph(), Money::new(99_999.0, "VND"));
+    }
+
+    #[test]
+    fn negative_amounts_keep_the_sign_before_the_symbol() {
+        assert_eq!(Money::new(-1234.5, "USD").to_string(), "-$1,234.50");
+    }
+}
@@ -1,55 +1,101 @@
-use crate::models::{FacebookAccount, TelegramConfig, AdThresholds, AdAccountMetrics};
-use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use crate::models::{FacebookAccount, TelegramConfig, AdAccountMetrics, BudgetChange, BudgetScalingRule, ControlLevel, InsightsWindow, ThresholdRule, TokenInfo, TokenStatus, ExchangedToken};
+use crate::constants::FB_DEFAULT_CONVERSION_ACTION_TYPE;
+use crate::secret::SecretString;
+use crate::config::{DatabasePoolConfig, DatabaseTlsConfig, SslMode};
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslVerifyMode};
 use postgres_openssl::MakeTlsConnector;
-use tokio_postgres::{Client, NoTls, Config};
-use std::error::Error;
+use deadpool_postgres::{Manager, ManagerConfig, Object, Pool, PoolError, RecyclingMethod, Runtime};
+use tokio_postgres::config::SslMode as PgSslMode;
+use tokio_postgres::Config;
+use std::time::Duration;
 use thiserror::Error;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use std::str::FromStr;
 
 #[derive(Error, Debug)]
 pub enum DatabaseError {
     #[error("Database connection error: {0}")]
-    ConnectionError(#[from] tokio_postgres::Error),
+    ConnectionError(tokio_postgres::Error),
+    #[error("Database query error: {0}")]
+    QueryError(#[from] tokio_postgres::Error),
     #[error("SSL error: {0}")]
     SslError(#[from] openssl::error::ErrorStack),
+    #[error("Cannot load {0}: {1}")]
+    CertificateError(String, openssl::error::ErrorStack),
     #[error("Invalid connection string: {0}")]
     InvalidConnectionString(String),
+    #[error("Timed out waiting for a database connection: {0}")]
+    PoolTimeout(String),
+    #[error("Database pool error: {0}")]
+    PoolError(String),
 }
 
+impl From<PoolError> for DatabaseError {
+    fn from(error: PoolError) -> Self {
+        match error {
+            PoolError::Backend(e) => DatabaseError::ConnectionError(e),
+            PoolError::Timeout(kind) => DatabaseError::PoolTimeout(format!("{:?}", kind)),
+            other => DatabaseError::PoolError(other.to_string()),
+        }
+    }
+}
+
+/// Pooled Postgres connections. Connections that died (server restart, idle
+/// timeout at the provider) are checked on checkout and replaced, so a lost
+/// connection only fails the queries that were running on it.
 pub struct Database {
-    client: Client,
+    pool: Pool,
 }
 
 impl Database {
-    pub async fn new(database_url: &str) -> Result<Self, DatabaseError> {
-    // Configure SSL
-        let mut builder = SslConnector::builder(SslMethod::tls())?;
-        builder.set_verify(SslVerifyMode::NONE); // For development only, use proper verification in production
-        let connector = MakeTlsConnector::new(builder.build());
+    pub async fn new(
+        database_url: &str,
+        pool_config: &DatabasePoolConfig,
+        tls_config: &DatabaseTlsConfig,
+    ) -> Result<Self, DatabaseError> {
+        let connector = tls_connector(tls_config)?;
 
         // Parse the connection config from URL
         let mut config = Config::from_str(database_url)
             .map_err(|e| DatabaseError::InvalidConnectionString(e.to_string()))?;
+        config.connect_timeout(Duration::from_secs(pool_config.connect_timeout_secs));
+        config.ssl_mode(match tls_config.ssl_mode {
+            SslMode::Disable => PgSslMode::Disable,
+            SslMode::Prefer => PgSslMode::Prefer,
+            SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => PgSslMode::Require,
+        });
 
-        // Connect with SSL
-        let (client, connection) = config
-          .connect_timeout(std::time::Duration::from_secs(5))
-          .connect(connector)
-          .await
-          .map_err(DatabaseError::ConnectionError)?;
-        
-        // Spawn the connection handler
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                eprintln!("Database connection error: {}", e);
-            }
+        // Verified recycling runs a test query before a pooled connection is reused
+        let manager = Manager::from_config(config, connector, ManagerConfig {
+            recycling_method: RecyclingMethod::Verified,
         });
+        let pool = Pool::builder(manager)
+            .max_size(pool_config.max_size)
+            .runtime(Runtime::Tokio1)
+            .wait_timeout(Some(Duration::from_secs(pool_config.wait_timeout_secs)))
+            .create_timeout(Some(Duration::from_secs(pool_config.connect_timeout_secs)))
+            .recycle_timeout(Some(Duration::from_secs(pool_config.recycle_timeout_secs)))
+            .build()
+            .map_err(|e| DatabaseError::PoolError(e.to_string()))?;
 
-        Ok(Self { client })
+        let database = Self { pool };
+        database.health_check().await?;
+        Ok(database)
+    }
+
+    /// Checks out a connection and runs a trivial query on it.
+    pub async fn health_check(&self) -> Result<(), DatabaseError> {
+        self.client().await?.simple_query("SELECT 1").await?;
+        Ok(())
+    }
+
+    async fn client(&self) -> Result<Object, DatabaseError> {
+        Ok(self.pool.get().await?)
     }
 
     pub async fn get_facebook_accounts(&self) -> Result<Vec<FacebookAccount>, DatabaseError> {
-        let rows = self.client.query(
+        let rows = self.client().await?.query(
             "SELECT 
                 fa.id, 
                 fa.access_token, 
@@ -57,9 +103,19 @@ impl Database {
                 fa.is_active, 
                 fa.interval,
                 tc.bot_token,
-                tc.chat_id
+                tc.chat_id,
+                fa.insights_window,
+                fa.conversion_action_types,
+                fa.low_funds_threshold,
+                bs.target_cost_per_action,
+                bs.window_days,
+                bs.increase_pct,
+                bs.max_daily_increase_pct,
+                bs.max_daily_budget,
+                fa.last_run_at
              FROM facebook_accounts fa
              INNER JOIN telegram_config tc ON fa.telegram_config_id = tc.id
+             LEFT JOIN budget_scaling_rules bs ON bs.account_id = fa.id AND bs.is_enabled
              WHERE fa.is_active = true",
             &[],
         ).await?;
@@ -68,12 +124,23 @@ impl Database {
             .iter()
             .map(|row| FacebookAccount {
                 id: row.get(0),
-                access_token: row.get(1),
+                access_token: SecretString::new(row.get(1)),
                 account_id: row.get(2),
                 is_active: row.get(3),
                 interval: row.get(4),
+                insights_window: parse_insights_window(row.get(2), row.get(7)),
+                conversion_action_types: conversion_action_types(row.get(8)),
+                low_funds_threshold: row.get(9),
+                budget_scaling: row.get::<_, Option<f64>>(10).map(|target_cost_per_action| BudgetScalingRule {
+                    target_cost_per_action,
+                    window_days: row.get(11),
+                    increase_pct: row.get(12),
+                    max_daily_increase_pct: row.get(13),
+                    max_daily_budget: row.get(14),
+                }),
+                last_run_at: row.get(15),
                 telegram_config: TelegramConfig {
-                    bot_token: row.get(5),
+                    bot_token: SecretString::new(row.get(5)),
                     chat_id: row.get(6),
                 },
             })
@@ -82,8 +149,18 @@ impl Database {
         Ok(accounts)
     }
 
+    pub async fn record_run(&self, account_id: i32, started_at: DateTime<Utc>) -> Result<(), DatabaseError> {
+        self.client().await?.execute(
+            "UPDATE facebook_accounts SET last_run_at = $2 WHERE id = $1",
+            &[&account_id, &started_at],
+        ).await?;
+
+        Ok(())
+    }
+
+    #[allow(dead_code)]
     pub async fn update_metrics(&self, metrics: &AdAccountMetrics) -> Result<(), DatabaseError> {
-        self.client.execute(
+        self.client().await?.execute(
             "INSERT INTO account_metrics (account_id, spend, impressions, clicks, conversions, created_at)
              VALUES ($1, $2, $3, $4, $5, NOW())",
             &[
@@ -98,14 +175,250 @@ impl Database {
         Ok(())
     }
 
-    pub async fn get_ad_thresholds(&self) -> Result<AdThresholds, DatabaseError> {
-        let row = self.client.query_one(
-            "SELECT max_cost_per_action FROM ad_thresholds LIMIT 1",
-            &[],
+    /// Records that remaining funds are below the account's threshold. True
+    /// only when they were not already, i.e. when the alert should be sent.
+    pub async fn mark_low_funds(&self, account_id: i32) -> Result<bool, DatabaseError> {
+        let updated = self.client().await?.execute(
+            "UPDATE facebook_accounts SET low_funds_alerted_at = NOW()
+             WHERE id = $1 AND low_funds_alerted_at IS NULL",
+            &[&account_id],
+        ).await?;
+
+        Ok(updated > 0)
+    }
+
+    /// Records that remaining funds are not below the threshold (or no longer
+    /// watched), so the next drop is alerted again.
+    pub async fn clear_low_funds(&self, account_id: i32) -> Result<(), DatabaseError> {
+        self.client().await?.execute(
+            "UPDATE facebook_accounts SET low_funds_alerted_at = NULL
+             WHERE id = $1 AND low_funds_alerted_at IS NOT NULL",
+            &[&account_id],
+        ).await?;
+
+        Ok(())
+    }
+
+    pub async fn get_token_status(&self, account_id: i32) -> Result<TokenStatus, DatabaseError> {
+        let row = self.client().await?.query_one(
+            "SELECT access_token, token_is_valid, token_checked_at FROM facebook_accounts WHERE id = $1",
+            &[&account_id],
         ).await?;
 
-        Ok(AdThresholds {
-            max_cost_per_action: row.get(0),
+        Ok(TokenStatus {
+            access_token: SecretString::new(row.get(0)),
+            is_valid: row.get(1),
+            checked_at: row.get(2),
         })
     }
-} 
\ No newline at end of file
+
+    /// Stores a replacement access token. Facebook only issues one for a valid
+    /// token, so it counts as checked now and is next inspected on the regular
+    /// check interval.
+    pub async fn update_access_token(&self, account_id: i32, token: &ExchangedToken) -> Result<(), DatabaseError> {
+        self.client().await?.execute(
+            "UPDATE facebook_accounts SET
+                access_token = $2,
+                token_expires_at = COALESCE($3, token_expires_at),
+                token_is_valid = TRUE,
+                token_error = NULL,
+                token_checked_at = NOW()
+             WHERE id = $1",
+            &[&account_id, &token.access_token.expose(), &token.expires_at],
+        ).await?;
+
+        Ok(())
+    }
+
+    pub async fn update_token_status(&self, account_id: i32, info: &TokenInfo) -> Result<(), DatabaseError> {
+        self.client().await?.execute(
+            "UPDATE facebook_accounts SET
+                token_is_valid = $2,
+                token_type = $3,
+                token_scopes = $4,
+                token_expires_at = $5,
+                token_data_access_expires_at = $6,
+                token_error = $7,
+                token_checked_at = NOW()
+             WHERE id = $1",
+            &[
+                &account_id,
+                &info.is_valid,
+                &info.token_type,
+                &info.scopes,
+                &info.expires_at,
+                &info.data_access_expires_at,
+                &info.error,
+            ],
+        ).await?;
+
+        Ok(())
+    }
+
+    /// Daily budget of the ad set before the first raise recorded since `since`,
+    /// used to cap the total raise within a day.
+    pub async fn get_budget_before(&self, account_id: i32, adset_id: &str, since: DateTime<Utc>) -> Result<Option<f64>, DatabaseError> {
+        let row = self.client().await?.query_opt(
+            "SELECT old_budget FROM budget_changes
+             WHERE account_id = $1 AND adset_id = $2 AND changed_at >= $3
+             ORDER BY changed_at
+             LIMIT 1",
+            &[&account_id, &adset_id, &since],
+        ).await?;
+
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    pub async fn record_budget_change(&self, account_id: i32, change: &BudgetChange) -> Result<(), DatabaseError> {
+        self.client().await?.execute(
+            "INSERT INTO budget_changes
+                (account_id, adset_id, adset_name, old_budget, new_budget, currency, cost_per_action)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            &[
+                &account_id,
+                &change.adset_id,
+                &change.adset_name,
+                &change.old_budget.amount,
+                &change.new_budget.amount,
+                &change.new_budget.currency,
+                &change.cost_per_action,
+            ],
+        ).await?;
+
+        Ok(())
+    }
+
+    /// Time of the latest status change of every object of the account changed
+    /// since `since`, keyed by Graph object ID.
+    pub async fn get_recent_status_changes(&self, account_id: i32, since: DateTime<Utc>) -> Result<HashMap<String, DateTime<Utc>>, DatabaseError> {
+        let rows = self.client().await?.query(
+            "SELECT object_id, MAX(changed_at)
+             FROM status_changes
+             WHERE account_id = $1 AND changed_at >= $2
+             GROUP BY object_id",
+            &[&account_id, &since],
+        ).await?;
+
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    pub async fn record_status_change(
+        &self,
+        account_id: i32,
+        level: ControlLevel,
+        object_id: &str,
+        object_name: &str,
+        status: &str,
+    ) -> Result<(), DatabaseError> {
+        self.client().await?.execute(
+            "INSERT INTO status_changes (account_id, level, object_id, object_name, status)
+             VALUES ($1, $2, $3, $4, $5)",
+            &[&account_id, &level.to_string(), &object_id, &object_name, &status],
+        ).await?;
+
+        Ok(())
+    }
+
+    /// Global threshold rules and those of the given account, at every scope.
+    pub async fn get_threshold_rules(&self, account_id: i32) -> Result<Vec<ThresholdRule>, DatabaseError> {
+        let rows = self.client().await?.query(
+            "SELECT
+                account_id,
+                campaign_id,
+                adset_id,
+                max_cost_per_action,
+                resume_cost_per_action,
+                cooldown_hours,
+                control_level,
+                min_spend,
+                min_impressions,
+                min_age_hours,
+                min_conversions
+             FROM threshold_rules
+             WHERE account_id IS NULL OR account_id = $1",
+            &[&account_id],
+        ).await?;
+
+        let rules = rows
+            .iter()
+            .map(|row| ThresholdRule {
+                account_id: row.get(0),
+                campaign_id: row.get(1),
+                adset_id: row.get(2),
+                max_cost_per_action: row.get(3),
+                resume_cost_per_action: row.get(4),
+                cooldown_hours: row.get(5),
+                control_level: parse_control_level(row.get(6)),
+                min_spend: row.get(7),
+                min_impressions: row.get(8),
+                min_age_hours: row.get(9),
+                min_conversions: row.get(10),
+            })
+            .collect();
+
+        Ok(rules)
+    }
+}
+
+fn conversion_action_types(value: Option<Vec<String>>) -> Vec<String> {
+    match value {
+        Some(types) if !types.is_empty() => types,
+        _ => vec![FB_DEFAULT_CONVERSION_ACTION_TYPE.to_string()],
+    }
+}
+
+/// Falls back to the default window when the column is empty or malformed,
+/// so one bad row doesn't stop every account from being monitored.
+fn parse_insights_window(account_id: &str, value: Option<String>) -> InsightsWindow {
+    match value.as_deref().map(str::parse) {
+        Some(Ok(window)) => window,
+        Some(Err(e)) => {
+            eprintln!("Invalid insights window for account {}: {}", account_id, e);
+            InsightsWindow::default()
+        }
+        None => InsightsWindow::default(),
+    }
+}
+
+/// Builds the TLS connector for `sslmode`. Only `verify-ca` and `verify-full`
+/// check the server certificate, against the system roots plus `root_cert`.
+fn tls_connector(tls: &DatabaseTlsConfig) -> Result<MakeTlsConnector, DatabaseError> {
+    let load_error = |path: &std::path::Path| {
+        let path = path.display().to_string();
+        move |e| DatabaseError::CertificateError(path, e)
+    };
+
+    let mut builder = SslConnector::builder(SslMethod::tls())?;
+    if let Some(path) = &tls.root_cert {
+        builder.set_ca_file(path).map_err(load_error(path))?;
+    }
+    if let (Some(cert), Some(key)) = (&tls.client_cert, &tls.client_key) {
+        builder.set_certificate_chain_file(cert).map_err(load_error(cert))?;
+        builder.set_private_key_file(key, SslFiletype::PEM).map_err(load_error(key))?;
+        builder.check_private_key().map_err(load_error(key))?;
+    }
+    if !tls.ssl_mode.verifies_certificate() {
+        builder.set_verify(SslVerifyMode::NONE); // Local development only, see DB_SSL_ALLOW_INSECURE
+    }
+
+    let mut connector = MakeTlsConnector::new(builder.build());
+    if tls.ssl_mode == SslMode::VerifyCa {
+        connector.set_callback(|config, _| {
+            config.set_verify_hostname(false);
+            Ok(())
+        });
+    }
+    Ok(connector)
+}
+
+/// A malformed level is logged and treated as unset, so it is inherited.
+fn parse_control_level(value: Option<String>) -> Option<ControlLevel> {
+    match value.as_deref().map(str::parse) {
+        Some(Ok(level)) => Some(level),
+        Some(Err(e)) => {
+            eprintln!("Invalid threshold control level: {}", e);
+            None
+        }
+        None => None,
+    }
+}
@@ -1,14 +1,30 @@
-use crate::models::AdAccountMetrics;
-use reqwest::Client;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
-use std::error::Error;
+use serde_json::{json, Value};
+use chrono::{DateTime, FixedOffset, NaiveDate, Offset, Utc};
+use std::fmt;
+use tokio::time::Instant;
+use std::sync::Arc;
 use thiserror::Error;
+use crate::config::{AppCredentials, GraphApiConfig, PaginationConfig, RetryConfig};
+use crate::rate_limit::{AccountUsage, RateLimiter};
+use crate::retry::{with_retry, Retryable};
 use crate::constants::{
-    FACEBOOK_API_VERSION, 
-    FACEBOOK_BASE_URL, 
-    FB_AD_FIELDS, 
-    FB_ACCOUNT_FIELDS
+    FACEBOOK_API_VERSION,
+    FACEBOOK_BASE_URL,
+    FB_AD_FIELDS,
+    FB_ADSET_FIELDS,
+    FB_CAMPAIGN_FIELDS,
+    FB_AD_INSIGHTS_FIELDS,
+    FB_ACCOUNT_FIELDS,
+    FB_BATCH_MAX_REQUESTS
 };
+use crate::currency::Money;
+use crate::models::{ControlLevel, ExchangedToken, InsightsWindow, TokenInfo};
+use crate::secret::{redact_reqwest, SecretString};
 
 #[derive(Error, Debug)]
 pub enum FacebookApiError {
@@ -18,6 +34,112 @@ pub enum FacebookApiError {
     InsufficientFunds,
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+    #[error("Network error: {0}")]
+    Network(String),
+    #[error("HTTP {0}: {1}")]
+    HttpStatus(u16, String),
+    #[error("Access token expired: {0}")]
+    TokenExpired(GraphError),
+    #[error("Invalid access token: {0}")]
+    InvalidToken(GraphError),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(GraphError),
+    #[error("Rate limited: {0}")]
+    RateLimited(GraphError),
+    #[error("Temporarily unavailable: {0}")]
+    TemporarilyUnavailable(GraphError),
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(GraphError),
+    #[error("Graph API error: {0}")]
+    Graph(GraphError),
+}
+
+impl FacebookApiError {
+    /// Classifies a Graph `error` object by its code and subcode.
+    pub fn from_graph(error: GraphError) -> Self {
+        match (error.code, error.error_subcode) {
+            (190, Some(463)) => Self::TokenExpired(error),
+            (102 | 190, _) => Self::InvalidToken(error),
+            (3 | 10 | 200..=299, _) => Self::PermissionDenied(error),
+            (4 | 17 | 32 | 613 | 80000..=80014, _) => Self::RateLimited(error),
+            (1 | 2, _) => Self::TemporarilyUnavailable(error),
+            _ if error.is_transient => Self::TemporarilyUnavailable(error),
+            (100, _) => Self::InvalidParameter(error),
+            _ => Self::Graph(error),
+        }
+    }
+
+    /// True when the account's token can no longer be used and has to be reconnected.
+    pub fn is_auth_error(&self) -> bool {
+        matches!(self, Self::TokenExpired(_) | Self::InvalidToken(_))
+    }
+}
+
+impl Retryable for FacebookApiError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::Network(_) | Self::TemporarilyUnavailable(_) => true,
+            Self::HttpStatus(status, _) => *status >= 500,
+            Self::Graph(e) => e.is_transient,
+            _ => false,
+        }
+    }
+}
+
+/// The `error` envelope Graph API returns alongside a non-2xx status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphError {
+    pub message: String,
+    #[serde(rename = "type", default)]
+    pub error_type: String,
+    pub code: i64,
+    pub error_subcode: Option<i64>,
+    pub fbtrace_id: Option<String>,
+    #[serde(default)]
+    pub is_transient: bool,
+}
+
+impl GraphError {
+    pub fn from_response(body: &Value) -> Option<Self> {
+        serde_json::from_value(body.get("error")?.clone()).ok()
+    }
+
+    /// A throttle imposed by our own `RateLimiter` rather than reported by
+    /// Facebook, so it carries no Graph error code.
+    pub fn local_throttle(message: String) -> Self {
+        Self {
+            message,
+            error_type: "RateLimiter".to_string(),
+            code: 0,
+            error_subcode: None,
+            fbtrace_id: None,
+            is_transient: false,
+        }
+    }
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (code {}", self.message, self.code)?;
+        if let Some(subcode) = self.error_subcode {
+            write!(f, ", subcode {}", subcode)?;
+        }
+        if let Some(trace) = &self.fbtrace_id {
+            write!(f, ", fbtrace_id {}", trace)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// An ad, ad set or campaign the worker can judge and pause or resume.
+pub trait AdObject: Sync {
+    fn level(&self) -> ControlLevel;
+    fn id(&self) -> &str;
+    fn name(&self) -> &str;
+    fn status(&self) -> &str;           // Status set on the object itself
+    fn effective_status(&self) -> &str; // Status after its parents are taken into account
+    fn created_time(&self) -> Option<DateTime<Utc>>;
+    fn performance(&self) -> &Performance;
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,8 +148,123 @@ pub struct AdData {
     pub name: String,
     pub status: String,
     pub effective_status: String,
+    pub created_time: Option<DateTime<Utc>>,
+    pub adset_id: String,
+    pub campaign_id: String,
+    pub performance: Performance,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdSet {
+    pub id: String,
+    pub name: String,
+    pub status: String,
+    pub effective_status: String,
+    pub created_time: Option<DateTime<Utc>>,
+    pub campaign_id: String,
+    pub daily_budget: Option<Money>,    // None when the budget is set on the campaign
+    pub lifetime_budget: Option<Money>,
+    pub bid_strategy: Option<String>,   // e.g. LOWEST_COST_WITHOUT_CAP, COST_CAP
+    pub optimization_goal: Option<String>,
+    pub performance: Performance,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Campaign {
+    pub id: String,
+    pub name: String,
+    pub status: String,
+    pub effective_status: String,
+    pub created_time: Option<DateTime<Utc>>,
+    pub objective: Option<String>,      // e.g. OUTCOME_LEADS, OUTCOME_SALES
+    pub daily_budget: Option<Money>,    // Only set with campaign budget optimization
+    pub lifetime_budget: Option<Money>,
+    pub bid_strategy: Option<String>,
+    pub performance: Performance,
+}
+
+macro_rules! impl_ad_object {
+    ($type:ty, $level:expr) => {
+        impl AdObject for $type {
+            fn level(&self) -> ControlLevel {
+                $level
+            }
+            fn id(&self) -> &str {
+                &self.id
+            }
+            fn name(&self) -> &str {
+                &self.name
+            }
+            fn status(&self) -> &str {
+                &self.status
+            }
+            fn effective_status(&self) -> &str {
+                &self.effective_status
+            }
+            fn created_time(&self) -> Option<DateTime<Utc>> {
+                self.created_time
+            }
+            fn performance(&self) -> &Performance {
+                &self.performance
+            }
+        }
+    };
+}
+
+impl_ad_object!(AdData, ControlLevel::Ad);
+impl_ad_object!(AdSet, ControlLevel::AdSet);
+impl_ad_object!(Campaign, ControlLevel::Campaign);
+
+/// Campaigns, ad sets and ads of an account with their insights, plus its balance.
+#[derive(Debug)]
+pub struct AccountSnapshot {
+    pub balance: AccountBalance,
+    pub campaigns: Vec<Campaign>,
+    pub ad_sets: Vec<AdSet>,
+    pub ads: Vec<AdData>,
+}
+
+impl AccountSnapshot {
+    /// Whether the ad itself, its ad set or its campaign is paused.
+    pub fn is_paused(&self, ad: &AdData) -> bool {
+        ad.status == "PAUSED"
+            || self.ad_sets.iter().any(|ad_set| ad_set.id == ad.adset_id && ad_set.status == "PAUSED")
+            || self.campaigns.iter().any(|campaign| campaign.id == ad.campaign_id && campaign.status == "PAUSED")
+    }
+}
+
+/// Insights of an ad, ad set or campaign over the account's insights window.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Performance {
     pub insights: Option<AdInsights>,
-    pub cost_per_action_type: Vec<CostPerAction>,
+    pub actions: Vec<ActionStat>,
+    pub cost_per_action_type: Vec<ActionStat>,
+}
+
+impl Performance {
+    /// Total number of actions of the given types.
+    pub fn action_count(&self, action_types: &[String]) -> f64 {
+        self.actions
+            .iter()
+            .filter(|stat| action_types.contains(&stat.action_type))
+            .filter_map(|stat| stat.value.parse::<f64>().ok())
+            .sum()
+    }
+
+    /// Cost per action across the given types: spend divided by their combined
+    /// count, or Facebook's own figure when only `cost_per_action_type` is present.
+    pub fn cost_per_action(&self, action_types: &[String]) -> Option<f64> {
+        let count = self.action_count(action_types);
+        match &self.insights {
+            Some(insights) if count > 0.0 => Some(insights.spend / count),
+            _ => self
+                .cost_per_action_type
+                .iter()
+                .filter(|stat| action_types.contains(&stat.action_type))
+                .filter_map(|stat| stat.value.parse::<f64>().ok())
+                .reduce(f64::min),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,195 +273,938 @@ pub struct AdInsights {
     pub reach: i64,
     pub clicks: i64,
     pub spend: f64,
+    pub date_start: Option<String>, // Reporting window actually used by Facebook
+    pub date_stop: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CostPerAction {
+/// One entry of the Graph `actions` / `cost_per_action_type` arrays.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActionStat {
     pub action_type: String,
     pub value: String,
 }
 
+#[derive(Debug)]
+pub enum StatusUpdateOutcome {
+    Updated,
+    AlreadyInState,
+    Failed(String),
+}
+
+#[derive(Debug)]
+pub struct StatusUpdate {
+    pub level: ControlLevel,
+    pub id: String,
+    pub name: String,
+    pub status: String,
+    pub outcome: StatusUpdateOutcome,
+}
+
+#[derive(Debug)]
+pub struct BudgetUpdate {
+    pub adset_id: String,
+    pub adset_name: String,
+    pub old_budget: Money,
+    pub new_budget: Money,
+    pub outcome: StatusUpdateOutcome,
+}
+
+/// Outcome of every requested update, together with the account-wide error
+/// (throttling, revoked token) that stopped the rest, if any. Updates Facebook
+/// applied before that error are still listed as updated.
+#[derive(Debug)]
+pub struct UpdateReport<T> {
+    pub updates: Vec<T>,
+    pub error: Option<FacebookApiError>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AccountBalance {
     pub name: String,
     pub id: String,
     pub status: String,
     pub currency: String,
-    pub available_funds: String,
+    pub timezone: String,
+    pub timezone_offset_hours: f64,     // Current offset of `timezone` from UTC, e.g. 7.0 or -4.0
+    pub balance: Money,                 // Amount owed to Facebook, not funds left
+    pub amount_spent: Money,            // Lifetime spend, counted against `spend_cap`
+    pub spend_cap: Option<Money>,       // None when the account has no cap
+    pub is_prepaid: bool,
+    pub prepaid_balance: Option<Money>, // Funds left on a prepaid funding source
+    pub funding_source: Option<String>, // e.g. "Visa *1234" or "Available balance (฿1,000.00 THB)"
+}
+
+impl AccountBalance {
+    /// Current UTC offset of the account's time zone, in which Facebook
+    /// reports days and resets daily budgets.
+    pub fn utc_offset(&self) -> FixedOffset {
+        FixedOffset::east_opt((self.timezone_offset_hours * 3600.0).round() as i32)
+            .unwrap_or(Utc.fix())
+    }
+
+    /// Funds that can still be spent before delivery stops, or `None` when
+    /// neither a prepaid balance nor a spend cap limits the account.
+    pub fn remaining_funds(&self) -> Option<Money> {
+        let cap_left = self.spend_cap.as_ref().map(|cap| {
+            Money::new((cap.amount - self.amount_spent.amount).max(0.0), &self.currency)
+        });
+
+        match (cap_left, &self.prepaid_balance) {
+            (Some(cap_left), Some(prepaid)) => Some(Money::new(cap_left.amount.min(prepaid.amount), &self.currency)),
+            (Some(cap_left), None) => Some(cap_left),
+            (None, Some(prepaid)) => Some(prepaid.clone()),
+            (None, None) => None,
+        }
+    }
 }
 
 pub struct FacebookAPI {
     client: Client,
-    access_token: String,
+    access_token: SecretString,
     account_id: String,
     base_url: String,
+    pagination: PaginationConfig,
+    retry: RetryConfig,
+    app: Option<AppCredentials>,
+    rate_limiter: Arc<RateLimiter>,
+    deadline: Option<Instant>, // Requests the rate limiter holds past this fail instead of waiting
+}
+
+/// Walks a Graph API edge page by page, following `paging.cursors.after`
+/// until there is no `paging.next` or `max_items` have been returned.
+pub struct GraphPager<'a> {
+    api: &'a FacebookAPI,
+    edge: String,
+    params: Vec<(String, String)>,
+    after: Option<String>,
+    fetched: usize,
+    done: bool,
+}
+
+impl GraphPager<'_> {
+    pub async fn next_page(&mut self) -> Result<Option<Vec<Value>>, FacebookApiError> {
+        let Some(params) = self.page_params() else {
+            return Ok(None);
+        };
+
+        let url = format!("{}/{}", self.api.base_url, self.edge);
+        let page = self.api.get_json(&url, &params).await?;
+        self.accept_page(page).map(Some)
+    }
+
+    pub async fn collect_all(mut self) -> Result<Vec<Value>, FacebookApiError> {
+        let mut result = Vec::new();
+        while let Some(items) = self.next_page().await? {
+            result.extend(items);
+        }
+        Ok(result)
+    }
+
+    /// Collects the remaining items, starting from a first page fetched elsewhere
+    /// (e.g. in a batch call).
+    pub async fn collect_from(mut self, first_page: Value) -> Result<Vec<Value>, FacebookApiError> {
+        let mut result = self.accept_page(first_page)?;
+        while let Some(items) = self.next_page().await? {
+            result.extend(items);
+        }
+        Ok(result)
+    }
+
+    /// Query parameters for the next page, or `None` when paging is finished.
+    fn page_params(&self) -> Option<Vec<(String, String)>> {
+        let remaining = self.api.pagination.max_items.saturating_sub(self.fetched);
+        if self.done || remaining == 0 {
+            return None;
+        }
+
+        let limit = remaining.min(self.api.pagination.page_size as usize);
+        let mut params = self.params.clone();
+        params.push(("limit".to_string(), limit.to_string()));
+        if let Some(after) = &self.after {
+            params.push(("after".to_string(), after.clone()));
+        }
+        Some(params)
+    }
+
+    /// Takes the items of a fetched page and remembers the cursor to the next one.
+    fn accept_page(&mut self, page: Value) -> Result<Vec<Value>, FacebookApiError> {
+        let remaining = self.api.pagination.max_items.saturating_sub(self.fetched);
+        let mut items = match page.get("data") {
+            Some(Value::Array(data)) => data.clone(),
+            _ => return Err(FacebookApiError::InvalidResponse(
+                format!("missing data array in page from {}", self.edge)
+            )),
+        };
+        items.truncate(remaining);
+        self.fetched += items.len();
+
+        self.after = page["paging"]["cursors"]["after"].as_str().map(str::to_string);
+        if page["paging"].get("next").is_none() || self.after.is_none() || items.is_empty() {
+            self.done = true;
+        }
+
+        Ok(items)
+    }
+}
+
+/// One sub-request of a Graph API batch call. Later requests can use the
+/// result of a `name`d one through `depends_on` and a JSONPath reference in
+/// `relative_url`, e.g. `?ids={result=ads:$.data.*.id}`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchRequest {
+    pub method: &'static str,
+    pub relative_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<String>,
+    pub omit_response_on_success: bool,
+}
+
+impl BatchRequest {
+    pub fn get(edge: &str, params: &[(String, String)]) -> Self {
+        Self {
+            method: "GET",
+            relative_url: format!("{}?{}", edge, encode_params(params)),
+            body: None,
+            name: None,
+            depends_on: None,
+            omit_response_on_success: false,
+        }
+    }
+
+    pub fn post(edge: &str, params: &[(String, String)]) -> Self {
+        Self {
+            method: "POST",
+            relative_url: edge.to_string(),
+            body: Some(encode_params(params)),
+            name: None,
+            depends_on: None,
+            omit_response_on_success: false,
+        }
+    }
 }
 
 impl FacebookAPI {
-    pub fn new(access_token: String, account_id: String) -> Self {
+    pub fn new(
+        access_token: SecretString,
+        account_id: String,
+        config: &GraphApiConfig,
+        rate_limiter: Arc<RateLimiter>,
+    ) -> Self {
         Self {
             client: Client::new(),
             access_token,
             account_id,
             base_url: format!("{}/{}", FACEBOOK_BASE_URL, FACEBOOK_API_VERSION),
+            pagination: config.pagination,
+            retry: config.retry,
+            app: config.app.clone(),
+            rate_limiter,
+            deadline: None,
+        }
+    }
+
+    /// Fails requests with `RateLimited` when the rate limiter would hold
+    /// them past `deadline`, e.g. the end of the account's run.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Latest Marketing API usage reported for this account.
+    pub fn usage(&self) -> Option<AccountUsage> {
+        self.rate_limiter.usage(&self.account_id)
+    }
+
+    /// Returns a pager over `edge` (e.g. `act_123/ads`) with the given query parameters.
+    pub fn pager(&self, edge: &str, params: &[(&str, &str)]) -> GraphPager<'_> {
+        GraphPager {
+            api: self,
+            edge: edge.to_string(),
+            params: params
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            after: None,
+            fetched: 0,
+            done: false,
         }
     }
 
-    pub async fn get_ads(&self) -> Result<Vec<AdData>, FacebookApiError> {
-        let url = format!("{}/act_{}/ads", self.base_url, self.account_id);
-        
-        let response = self.client
-            .get(&url)
-            .query(&[
-                ("access_token", &self.access_token),
-                ("fields", &FB_AD_FIELDS.to_string()),
-            ])
+    async fn get_json(&self, url: &str, params: &[(String, String)]) -> Result<Value, FacebookApiError> {
+        self.send_json(
+            self.client
+                .get(url)
+                .query(params),
+        ).await
+    }
+
+    /// Sends a request and decodes the body, turning Graph `error` envelopes
+    /// and non-2xx statuses into a `FacebookApiError`. Transient failures are
+    /// retried according to the configured retry policy.
+    async fn send_json(&self, request: RequestBuilder) -> Result<Value, FacebookApiError> {
+        with_retry(&self.retry, "Graph API request", || async {
+            let attempt = request.try_clone().ok_or_else(|| {
+                FacebookApiError::RequestFailed("request cannot be cloned for retry".to_string())
+            })?;
+            self.send_once(attempt).await
+        }).await
+    }
+
+    async fn send_once(&self, request: RequestBuilder) -> Result<Value, FacebookApiError> {
+        self.rate_limiter
+            .wait_turn(&self.account_id, self.deadline)
+            .await
+            .map_err(|delay| {
+                FacebookApiError::RateLimited(GraphError::local_throttle(format!(
+                    "requests paused for another {:?}, past the end of this run",
+                    delay
+                )))
+            })?;
+
+        // The token travels in the Authorization header so it never ends up in
+        // a URL that reqwest errors or logs could echo back
+        let request = match &self.app {
+            Some(app) => request.query(&[("appsecret_proof", appsecret_proof(app, &self.access_token)?)]),
+            None => request,
+        };
+
+        let response = request
+            .bearer_auth(self.access_token.expose())
             .send()
             .await
-            .map_err(|e| FacebookApiError::RequestFailed(e.to_string()))?;
+            .map_err(|e| {
+                let retryable = e.is_timeout() || e.is_connect();
+                let message = self.access_token.redact(&redact_reqwest(e));
+                if retryable {
+                    FacebookApiError::Network(message)
+                } else {
+                    FacebookApiError::RequestFailed(message)
+                }
+            })?;
+
+        self.rate_limiter.record(&self.account_id, response.headers());
 
-        let ads: serde_json::Value = response
-            .json()
+        let status = response.status();
+        let text = response
+            .text()
             .await
-            .map_err(|e| FacebookApiError::InvalidResponse(e.to_string()))?;
+            .map_err(|e| FacebookApiError::Network(redact_reqwest(e)))?;
 
-        let mut result = Vec::new();
-        
-        if let Some(data) = ads["data"].as_array() {
-            for ad in data {
-                let insights = ad.get("insights")
-                    .and_then(|i| i.get("data"))
-                    .and_then(|d| d.get(0))
-                    .unwrap_or(&serde_json::Value::Null);
-
-                let cost_per_action = insights
-                    .get("cost_per_action_type")
-                    .and_then(|actions| {
-                        actions.as_array()?.iter().find(|action| {
-                            action.get("action_type")
-                                .and_then(|t| t.as_str())
-                                == Some("offsite_conversion.fb_pixel_custom")
-                        })
-                    })
-                    .and_then(|action| action.get("value"))
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("0")
-                    .to_string();
-
-                result.push(AdData {
-                    id: ad["id"].as_str().unwrap_or("").to_string(),
-                    name: ad["name"].as_str().unwrap_or("").to_string(),
-                    status: ad["status"].as_str().unwrap_or("").to_string(),
-                    effective_status: ad["effective_status"].as_str().unwrap_or("").to_string(),
-                    insights: Some(AdInsights {
-                        impressions: insights.get("impressions").and_then(|v| v.as_i64()).unwrap_or(0),
-                        reach: insights.get("reach").and_then(|v| v.as_i64()).unwrap_or(0),
-                        clicks: insights.get("clicks").and_then(|v| v.as_i64()).unwrap_or(0),
-                        spend: insights.get("spend").and_then(|v| v.as_str()).and_then(|s| s.parse().ok()).unwrap_or(0.0),
-                    }),
-                    cost_per_action_type: vec![CostPerAction {
-                        action_type: "offsite_conversion.fb_pixel_custom".to_string(),
-                        value: cost_per_action,
-                    }],
-                });
+        self.decode_body(status.as_u16(), &text)
+    }
+
+    /// Turns a response body into JSON, or into the `FacebookApiError` its Graph
+    /// `error` envelope or HTTP status describes. Only the text kept in errors is
+    /// redacted, so a body that legitimately carries a token (token exchange)
+    /// is returned intact.
+    fn decode_body(&self, status: u16, text: &str) -> Result<Value, FacebookApiError> {
+        let success = (200..300).contains(&status);
+
+        let body: Value = match serde_json::from_str(text) {
+            Ok(body) => body,
+            Err(_) if !success => return Err(FacebookApiError::HttpStatus(status, self.access_token.redact(text))),
+            Err(e) => return Err(FacebookApiError::InvalidResponse(e.to_string())),
+        };
+
+        if let Some(mut error) = GraphError::from_response(&body) {
+            error.message = self.access_token.redact(&error.message);
+            let error = FacebookApiError::from_graph(error);
+            if let FacebookApiError::RateLimited(graph) = &error {
+                self.rate_limiter.throttled(&self.account_id, graph.code);
             }
+            return Err(error);
+        }
+        if !success {
+            return Err(FacebookApiError::HttpStatus(status, self.access_token.redact(text)));
         }
 
-        Ok(result)
+        Ok(body)
     }
 
-    pub async fn update_ad_status(&self, ad_name: &str, status: &str) -> Result<Vec<AdData>, FacebookApiError> {
-        let status = match status.to_lowercase().as_str() {
-            "a" | "active" => "ACTIVE",
-            "p" | "paused" => "PAUSED",
-            _ => return Err(FacebookApiError::RequestFailed("Invalid status value".to_string())),
-        };
+    /// Sends `requests` through the Graph `batch` endpoint, at most
+    /// `FB_BATCH_MAX_REQUESTS` per call, and returns one result per request in
+    /// the same order. Requests only depend on others within the same chunk.
+    pub async fn batch(&self, requests: &[BatchRequest]) -> Result<Vec<Result<Value, FacebookApiError>>, FacebookApiError> {
+        let mut results = Vec::with_capacity(requests.len());
 
-        let url = format!("{}/act_{}/ads", self.base_url, self.account_id);
-        let response = self.client
-            .get(&url)
-            .query(&[
-                ("access_token", &self.access_token),
-                ("fields", &"id,name,status,effective_status".to_string()),
-                ("filtering", &format!("[{{'field':'name','operator':'CONTAIN','value':'{}'}}]", ad_name)),
-            ])
-            .send()
-            .await
-            .map_err(|e| FacebookApiError::RequestFailed(e.to_string()))?;
-
-        let ads: serde_json::Value = response.json().await
-            .map_err(|e| FacebookApiError::InvalidResponse(e.to_string()))?;
-
-        let mut updated_ads = Vec::new();
-
-        if let Some(data) = ads["data"].as_array() {
-            for ad in data {
-                let ad_id = ad["id"].as_str().unwrap_or("");
-                if ad["effective_status"] != status {
-                    // Update ad status
-                    let update_url = format!("{}/{}", self.base_url, ad_id);
-                    let _update_response = self.client
-                        .post(&update_url)
-                        .query(&[
-                            ("access_token", &self.access_token),
-                            ("status", &status.to_string()),
-                        ])
-                        .send()
-                        .await
-                        .map_err(|e| FacebookApiError::RequestFailed(e.to_string()))?;
-
-                    updated_ads.push(AdData {
-                        id: ad_id.to_string(),
-                        name: ad["name"].as_str().unwrap_or("").to_string(),
-                        status: status.to_string(),
-                        effective_status: status.to_string(),
-                        insights: None,
-                        cost_per_action_type: vec![],
-                    });
+        for chunk in requests.chunks(FB_BATCH_MAX_REQUESTS) {
+            let batch = serde_json::to_string(chunk)
+                .map_err(|e| FacebookApiError::InvalidResponse(e.to_string()))?;
+            let response = self.send_json(
+                self.client
+                    .post(&self.base_url)
+                    .form(&[("batch", batch.as_str()), ("include_headers", "false")]),
+            ).await?;
+
+            let responses = response.as_array().filter(|r| r.len() == chunk.len()).ok_or_else(|| {
+                FacebookApiError::InvalidResponse(format!("expected {} batch responses, got {}", chunk.len(), response))
+            })?;
+
+            for item in responses {
+                // null: the request was skipped because one it depends on failed
+                if item.is_null() {
+                    results.push(Err(FacebookApiError::InvalidResponse(
+                        "no response for batch request, a request it depends on failed".to_string(),
+                    )));
+                    continue;
                 }
+                let code = item["code"].as_u64().unwrap_or(0) as u16;
+                results.push(self.decode_body(code, item["body"].as_str().unwrap_or("")));
             }
         }
 
-        Ok(updated_ads)
+        Ok(results)
     }
 
-    pub async fn get_ad_account_balance(&self) -> Result<AccountBalance, FacebookApiError> {
-        let url = format!("{}/act_{}", self.base_url, self.account_id);
-        
-        let response = self.client
-            .get(&url)
-            .query(&[
-                ("access_token", &self.access_token),
-                ("fields", &FB_ACCOUNT_FIELDS.to_string()),
-            ])
-            .send()
-            .await
-            .map_err(|e| FacebookApiError::RequestFailed(e.to_string()))?;
+    /// Fetches every campaign, ad set and ad with insights aggregated over
+    /// `window`, together with the account balance. The account and the first
+    /// page of each edge are read in one batch call; further pages follow the cursor.
+    pub async fn get_account_snapshot(&self, window: &InsightsWindow) -> Result<AccountSnapshot, FacebookApiError> {
+        let insights = format!("insights{}.fields({})", insights_modifier(window), FB_AD_INSIGHTS_FIELDS);
+        let campaign_fields = format!("{},{}", FB_CAMPAIGN_FIELDS, insights);
+        let ad_set_fields = format!("{},{}", FB_ADSET_FIELDS, insights);
+        let ad_fields = format!("{},{}", FB_AD_FIELDS, insights);
 
-        let account: serde_json::Value = response
-            .json()
-            .await
-            .map_err(|e| FacebookApiError::InvalidResponse(e.to_string()))?;
+        let campaign_pager = self.pager(&format!("act_{}/campaigns", self.account_id), &[("fields", &campaign_fields)]);
+        let ad_set_pager = self.pager(&format!("act_{}/adsets", self.account_id), &[("fields", &ad_set_fields)]);
+        let ad_pager = self.pager(&format!("act_{}/ads", self.account_id), &[("fields", &ad_fields)]);
 
-        let balance = account["balance"].as_f64().unwrap_or(0.0);
-        let currency = account["currency"].as_str().unwrap_or("THB");
-        
-        let balance_in_currency = if currency == "THB" {
-            balance / 100.0  // Convert satang to baht
-        } else {
-            balance / 100.0  // Default conversion
+        let mut requests = vec![BatchRequest::get(
+            &format!("act_{}", self.account_id),
+            &[("fields".to_string(), FB_ACCOUNT_FIELDS.to_string())],
+        )];
+        for pager in [&campaign_pager, &ad_set_pager, &ad_pager] {
+            requests.push(BatchRequest::get(&pager.edge, &pager.page_params().unwrap_or_default()));
+        }
+
+        let mut results = self.batch(&requests).await?.into_iter();
+        let (Some(account), Some(campaigns), Some(ad_sets), Some(ads)) =
+            (results.next(), results.next(), results.next(), results.next())
+        else {
+            return Err(FacebookApiError::InvalidResponse("incomplete batch response".to_string()));
+        };
+
+        let balance = parse_account_balance(&account?)?;
+        let currency = balance.currency.clone();
+        let campaigns = campaign_pager.collect_from(campaigns?).await?;
+        let ad_sets = ad_set_pager.collect_from(ad_sets?).await?;
+        let ads = ad_pager.collect_from(ads?).await?;
+
+        Ok(AccountSnapshot {
+            balance,
+            campaigns: campaigns.iter().map(|campaign| parse_campaign(campaign, &currency)).collect(),
+            ad_sets: ad_sets.iter().map(|ad_set| parse_ad_set(ad_set, &currency)).collect(),
+            ads: ads.iter().map(parse_ad).collect(),
+        })
+    }
+
+    /// Applies each `(object, status)` change, identified by ID, in batch calls
+    /// and reports what happened to every ad, ad set or campaign. Per-object
+    /// failures are recorded in the outcome; errors that affect the whole
+    /// account (invalid token, rate limit) are returned instead.
+    pub async fn update_statuses(&self, changes: &[(&dyn AdObject, &str)]) -> Result<UpdateReport<StatusUpdate>, FacebookApiError> {
+        let mut updates = Vec::new();
+        let mut pending = Vec::new();
+
+        for (object, status) in changes {
+            let status = parse_status(status)?;
+            let update = StatusUpdate {
+                level: object.level(),
+                id: object.id().to_string(),
+                name: object.name().to_string(),
+                status: status.to_string(),
+                outcome: StatusUpdateOutcome::AlreadyInState,
+            };
+            if object.status() != status {
+                pending.push(updates.len());
+            }
+            updates.push(update);
+        }
+
+        let requests: Vec<BatchRequest> = pending
+            .iter()
+            .map(|&i| BatchRequest::post(&updates[i].id, &[("status".to_string(), updates[i].status.clone())]))
+            .collect();
+        let (outcomes, error) = self.apply_updates(&requests).await;
+
+        for (&i, outcome) in pending.iter().zip(outcomes) {
+            updates[i].outcome = outcome;
+        }
+
+        Ok(UpdateReport { updates, error })
+    }
+
+    /// Sets the daily budget of each ad set in batch calls. Per-ad-set failures
+    /// are recorded in the outcome; an account-wide error is reported alongside
+    /// the outcomes.
+    pub async fn update_daily_budgets(&self, changes: &[(&AdSet, Money)]) -> UpdateReport<BudgetUpdate> {
+        let requests: Vec<BatchRequest> = changes
+            .iter()
+            .map(|(ad_set, budget)| BatchRequest::post(&ad_set.id, &[("daily_budget".to_string(), budget.to_graph())]))
+            .collect();
+        let (outcomes, error) = self.apply_updates(&requests).await;
+
+        let updates = changes
+            .iter()
+            .zip(outcomes)
+            .map(|((ad_set, budget), outcome)| BudgetUpdate {
+                adset_id: ad_set.id.clone(),
+                adset_name: ad_set.name.clone(),
+                old_budget: ad_set.daily_budget.clone().unwrap_or_else(|| Money::new(0.0, &budget.currency)),
+                new_budget: budget.clone(),
+                outcome,
+            })
+            .collect();
+
+        UpdateReport { updates, error }
+    }
+
+    /// Sends update requests one batch call at a time and returns one outcome
+    /// per request. The first account-wide error, from a sub-request or a whole
+    /// batch call, stops the remaining calls; requests not sent are reported
+    /// as failed.
+    async fn apply_updates(&self, requests: &[BatchRequest]) -> (Vec<StatusUpdateOutcome>, Option<FacebookApiError>) {
+        let mut outcomes = Vec::with_capacity(requests.len());
+        let mut error = None;
+
+        for chunk in requests.chunks(FB_BATCH_MAX_REQUESTS) {
+            if error.is_some() {
+                break;
+            }
+            match self.batch(chunk).await {
+                Ok(results) => outcomes.extend(results.into_iter().map(|result| update_outcome(result, &mut error))),
+                Err(e) => error = Some(e),
+            }
+        }
+
+        let reason = error.as_ref().map(|e| format!("not sent: {}", e)).unwrap_or_default();
+        outcomes.resize_with(requests.len(), || StatusUpdateOutcome::Failed(reason.clone()));
+
+        (outcomes, error)
+    }
+
+    /// Daily insights of each object from `since` to `until`, one `Performance`
+    /// per day with delivery, read in batch calls. The page size covers the
+    /// whole range, so one page holds every day.
+    pub async fn get_daily_performance(
+        &self,
+        ids: &[&str],
+        since: NaiveDate,
+        until: NaiveDate,
+    ) -> Result<Vec<Result<Vec<Performance>, FacebookApiError>>, FacebookApiError> {
+        let time_range = json!({"since": since.to_string(), "until": until.to_string()}).to_string();
+        let params = [
+            ("fields".to_string(), FB_AD_INSIGHTS_FIELDS.to_string()),
+            ("time_range".to_string(), time_range),
+            ("time_increment".to_string(), "1".to_string()),
+            ("limit".to_string(), ((until - since).num_days() + 1).max(1).to_string()),
+        ];
+        let requests: Vec<BatchRequest> = ids
+            .iter()
+            .map(|id| BatchRequest::get(&format!("{}/insights", id), &params))
+            .collect();
+
+        let results = self.batch(&requests).await?;
+        Ok(results
+            .into_iter()
+            .map(|result| {
+                result.map(|page| {
+                    page["data"]
+                        .as_array()
+                        .map(|rows| rows.iter().map(parse_insights_row).collect())
+                        .unwrap_or_default()
+                })
+            })
+            .collect())
+    }
+
+    /// Sets the status of every ad whose name is exactly `ad_name`, for manual
+    /// commands that only know the ad by name. Automated decisions use
+    /// `update_statuses` with the ad IDs instead.
+    #[allow(dead_code)]
+    pub async fn update_ad_status_by_name(&self, ad_name: &str, status: &str) -> Result<UpdateReport<StatusUpdate>, FacebookApiError> {
+        let filtering = json!([{"field": "name", "operator": "EQUAL", "value": ad_name}]).to_string();
+        let ads: Vec<AdData> = self
+            .pager(
+                &format!("act_{}/ads", self.account_id),
+                &[
+                    ("fields", FB_AD_FIELDS),
+                    ("filtering", &filtering),
+                ],
+            )
+            .collect_all()
+            .await?
+            .iter()
+            .map(parse_ad)
+            // Re-check the name in case the filter is ever applied loosely
+            .filter(|ad| ad.name == ad_name)
+            .collect();
+
+        let changes: Vec<(&dyn AdObject, &str)> = ads.iter().map(|ad| (ad as &dyn AdObject, status)).collect();
+        self.update_statuses(&changes).await
+    }
+
+    /// Inspects this client's own access token with `/debug_token`. A token
+    /// Facebook rejects outright is reported as invalid rather than as an error.
+    pub async fn debug_token(&self) -> Result<TokenInfo, FacebookApiError> {
+        // A POST with `method=GET` keeps the inspected token in the body, out of the URL
+        let url = format!("{}/debug_token", self.base_url);
+        let request = self
+            .client
+            .post(&url)
+            .form(&[("method", "GET"), ("input_token", self.access_token.expose())]);
+        let response = match self.send_json(request).await {
+            Ok(response) => response,
+            Err(e) if e.is_auth_error() => return Ok(TokenInfo::invalid(e.to_string())),
+            Err(e) => return Err(e),
         };
 
-        Ok(AccountBalance {
-            name: account["name"].as_str().unwrap_or("Unknown").to_string(),
-            id: account["id"].as_str().unwrap_or("Unknown").to_string(),
-            status: if account["account_status"].as_i64().unwrap_or(0) == 1 {
-                "Active".to_string()
-            } else {
-                "Inactive".to_string()
-            },
-            currency: currency.to_string(),
-            available_funds: format!("฿{:.2}", balance_in_currency),
+        let data = &response["data"];
+        let timestamp = |field: &str| {
+            data[field]
+                .as_i64()
+                .filter(|ts| *ts > 0) // 0 means the token never expires
+                .and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0))
+        };
+
+        Ok(TokenInfo {
+            is_valid: data["is_valid"].as_bool().unwrap_or(false),
+            token_type: data["type"].as_str().map(str::to_string),
+            scopes: data["scopes"]
+                .as_array()
+                .map(|scopes| scopes.iter().filter_map(|s| s.as_str().map(str::to_string)).collect())
+                .unwrap_or_default(),
+            expires_at: timestamp("expires_at"),
+            data_access_expires_at: timestamp("data_access_expires_at"),
+            error: data["error"]["message"].as_str().map(str::to_string),
         })
     }
-} 
\ No newline at end of file
+
+    /// Exchanges this client's token for a long-lived one via `fb_exchange_token`.
+    /// Long-lived user tokens can be exchanged again to push their expiry out.
+    pub async fn exchange_token(&self) -> Result<ExchangedToken, FacebookApiError> {
+        let app = self.app.as_ref().ok_or_else(|| {
+            FacebookApiError::RequestFailed("FB_APP_ID and FB_APP_SECRET are required to exchange tokens".to_string())
+        })?;
+
+        // Sent as a form body so the app secret and token stay out of the URL
+        let url = format!("{}/oauth/access_token", self.base_url);
+        let response = self.send_json(
+            self.client
+                .post(&url)
+                .form(&[
+                    ("grant_type", "fb_exchange_token"),
+                    ("client_id", app.app_id.as_str()),
+                    ("client_secret", app.app_secret.expose()),
+                    ("fb_exchange_token", self.access_token.expose()),
+                ]),
+        ).await?;
+
+        let access_token = response["access_token"]
+            .as_str()
+            .ok_or_else(|| FacebookApiError::InvalidResponse("token exchange returned no access_token".to_string()))?;
+
+        // Saving it again would gain nothing
+        if access_token == self.access_token.expose() {
+            return Err(FacebookApiError::InvalidResponse(
+                "token exchange returned the current token".to_string(),
+            ));
+        }
+
+        Ok(ExchangedToken {
+            access_token: SecretString::new(access_token.to_string()),
+            expires_at: response["expires_in"]
+                .as_i64()
+                .map(|secs| Utc::now() + chrono::Duration::seconds(secs)),
+        })
+    }
+}
+
+/// Field expansion modifier selecting the insights window, e.g. `.date_preset(last_7d)`.
+fn insights_modifier(window: &InsightsWindow) -> String {
+    match window {
+        InsightsWindow::TimeRange { since, until } => format!(
+            ".time_range({})",
+            json!({"since": since.to_string(), "until": until.to_string()})
+        ),
+        preset => format!(".date_preset({})", preset.date_preset().unwrap_or_default()),
+    }
+}
+
+/// HMAC-SHA256 of the access token keyed with the app secret, hex encoded.
+fn appsecret_proof(app: &AppCredentials, access_token: &SecretString) -> Result<String, FacebookApiError> {
+    let proof = (|| {
+        let key = PKey::hmac(app.app_secret.expose().as_bytes())?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+        signer.update(access_token.expose().as_bytes())?;
+        signer.sign_to_vec()
+    })()
+    .map_err(|e| FacebookApiError::RequestFailed(format!("appsecret_proof: {}", e)))?;
+
+    Ok(proof.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn parse_account_balance(account: &Value) -> Result<AccountBalance, FacebookApiError> {
+    // UNSETTLED: delivery has stopped until the outstanding balance is paid
+    if account["account_status"].as_i64() == Some(3) {
+        return Err(FacebookApiError::InsufficientFunds);
+    }
+
+    let currency = account["currency"].as_str().unwrap_or("THB");
+    let amount = |field: &str| {
+        Money::from_graph(&account[field], currency).unwrap_or_else(|| Money::new(0.0, currency))
+    };
+
+    let funding_source = account["funding_source_details"]["display_string"]
+        .as_str()
+        .map(str::to_string);
+    let is_prepaid = account["is_prepay_account"].as_bool().unwrap_or(false);
+    // The prepaid balance is only exposed through the funding source's display string
+    let prepaid_balance = if is_prepaid {
+        funding_source
+            .as_deref()
+            .and_then(parse_display_amount)
+            .map(|value| Money::new(value, currency))
+    } else {
+        None
+    };
+
+    Ok(AccountBalance {
+        name: account["name"].as_str().unwrap_or("Unknown").to_string(),
+        id: account["id"].as_str().unwrap_or("Unknown").to_string(),
+        status: if account["account_status"].as_i64().unwrap_or(0) == 1 {
+            "Active".to_string()
+        } else {
+            "Inactive".to_string()
+        },
+        currency: currency.to_string(),
+        timezone: account["timezone_name"].as_str().unwrap_or("Unknown").to_string(),
+        timezone_offset_hours: account["timezone_offset_hours_utc"].as_f64().unwrap_or(0.0),
+        balance: amount("balance"),
+        amount_spent: amount("amount_spent"),
+        // A spend cap of 0 means no cap
+        spend_cap: Some(amount("spend_cap")).filter(|cap| cap.amount > 0.0),
+        is_prepaid,
+        prepaid_balance,
+        funding_source,
+    })
+}
+
+/// Extracts the amount from a display string such as "Available balance (฿1,234.56 THB)".
+fn parse_display_amount(display: &str) -> Option<f64> {
+    let start = display.find(|c: char| c.is_ascii_digit())?;
+    let number: String = display[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == ',' || *c == '.')
+        .filter(|c| *c != ',')
+        .collect();
+    number.trim_end_matches('.').parse().ok()
+}
+
+/// Outcome of one update sub-request. Account-wide errors also fail the
+/// update, and the first of them is kept in `error`.
+fn update_outcome(result: Result<Value, FacebookApiError>, error: &mut Option<FacebookApiError>) -> StatusUpdateOutcome {
+    match result {
+        Ok(response) if response["success"].as_bool() == Some(true) => StatusUpdateOutcome::Updated,
+        Ok(response) => StatusUpdateOutcome::Failed(format!("update not acknowledged: {}", response)),
+        Err(e) => {
+            let outcome = StatusUpdateOutcome::Failed(e.to_string());
+            if error.is_none() && (e.is_auth_error() || matches!(e, FacebookApiError::RateLimited(_))) {
+                *error = Some(e);
+            }
+            outcome
+        }
+    }
+}
+
+fn encode_params(params: &[(String, String)]) -> String {
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish()
+}
+
+fn parse_status(status: &str) -> Result<&'static str, FacebookApiError> {
+    match status.to_lowercase().as_str() {
+        "a" | "active" => Ok("ACTIVE"),
+        "p" | "paused" => Ok("PAUSED"),
+        _ => Err(FacebookApiError::RequestFailed("Invalid status value".to_string())),
+    }
+}
+
+fn parse_ad(ad: &Value) -> AdData {
+    AdData {
+        id: string_field(ad, "id"),
+        name: string_field(ad, "name"),
+        status: string_field(ad, "status"),
+        effective_status: string_field(ad, "effective_status"),
+        created_time: parse_time(&ad["created_time"]),
+        adset_id: string_field(ad, "adset_id"),
+        campaign_id: string_field(ad, "campaign_id"),
+        performance: parse_performance(ad),
+    }
+}
+
+fn parse_ad_set(ad_set: &Value, currency: &str) -> AdSet {
+    AdSet {
+        id: string_field(ad_set, "id"),
+        name: string_field(ad_set, "name"),
+        status: string_field(ad_set, "status"),
+        effective_status: string_field(ad_set, "effective_status"),
+        created_time: parse_time(&ad_set["created_time"]),
+        campaign_id: string_field(ad_set, "campaign_id"),
+        daily_budget: parse_budget(&ad_set["daily_budget"], currency),
+        lifetime_budget: parse_budget(&ad_set["lifetime_budget"], currency),
+        bid_strategy: ad_set["bid_strategy"].as_str().map(str::to_string),
+        optimization_goal: ad_set["optimization_goal"].as_str().map(str::to_string),
+        performance: parse_performance(ad_set),
+    }
+}
+
+fn parse_campaign(campaign: &Value, currency: &str) -> Campaign {
+    Campaign {
+        id: string_field(campaign, "id"),
+        name: string_field(campaign, "name"),
+        status: string_field(campaign, "status"),
+        effective_status: string_field(campaign, "effective_status"),
+        created_time: parse_time(&campaign["created_time"]),
+        objective: campaign["objective"].as_str().map(str::to_string),
+        daily_budget: parse_budget(&campaign["daily_budget"], currency),
+        lifetime_budget: parse_budget(&campaign["lifetime_budget"], currency),
+        bid_strategy: campaign["bid_strategy"].as_str().map(str::to_string),
+        performance: parse_performance(campaign),
+    }
+}
+
+fn string_field(object: &Value, field: &str) -> String {
+    object[field].as_str().unwrap_or("").to_string()
+}
+
+/// Insights counts are returned as strings, e.g. `"impressions": "1234"`.
+fn count_field(insights: &Value, field: &str) -> i64 {
+    match &insights[field] {
+        Value::String(s) => s.parse().unwrap_or(0),
+        other => other.as_i64().unwrap_or(0),
+    }
+}
+
+/// Graph timestamps look like `2024-05-01T08:30:00+0700`.
+fn parse_time(value: &Value) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(value.as_str()?, "%Y-%m-%dT%H:%M:%S%z")
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Budgets come in the currency's offset units like other account amounts;
+/// "0" or a missing field means the budget is set elsewhere.
+fn parse_budget(value: &Value, currency: &str) -> Option<Money> {
+    Money::from_graph(value, currency).filter(|budget| budget.amount > 0.0)
+}
+
+/// Reads the first row of an object's expanded `insights` field.
+fn parse_performance(object: &Value) -> Performance {
+    let insights = object.get("insights")
+        .and_then(|i| i.get("data"))
+        .and_then(|d| d.get(0))
+        .unwrap_or(&Value::Null);
+
+    parse_insights_row(insights)
+}
+
+fn parse_insights_row(insights: &Value) -> Performance {
+    let action_stats = |field: &str| -> Vec<ActionStat> {
+        insights
+            .get(field)
+            .and_then(|stats| serde_json::from_value(stats.clone()).ok())
+            .unwrap_or_default()
+    };
+
+    Performance {
+        insights: Some(AdInsights {
+            impressions: count_field(insights, "impressions"),
+            reach: count_field(insights, "reach"),
+            clicks: count_field(insights, "clicks"),
+            spend: insights.get("spend").and_then(|v| v.as_str()).and_then(|s| s.parse().ok()).unwrap_or(0.0),
+            date_start: insights.get("date_start").and_then(|v| v.as_str()).map(str::to_string),
+            date_stop: insights.get("date_stop").and_then(|v| v.as_str()).map(str::to_string),
+        }),
+        actions: action_stats("actions"),
+        cost_per_action_type: action_stats("cost_per_action_type"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph_error(code: i64, error_subcode: Option<i64>, is_transient: bool) -> GraphError {
+        GraphError {
+            message: "error".to_string(),
+            error_type: "OAuthException".to_string(),
+            code,
+            error_subcode,
+            fbtrace_id: None,
+            is_transient,
+        }
+    }
+
+    #[test]
+    fn parse_display_amount_reads_grouped_numbers() {
+        assert_eq!(parse_display_amount("฿1,234.56"), Some(1234.56));
+        assert_eq!(parse_display_amount("$12."), Some(12.0));
+        assert_eq!(parse_display_amount("1,000 THB"), Some(1000.0));
+        assert_eq!(parse_display_amount("no balance"), None);
+    }
+
+    #[test]
+    fn account_offset_reads_fractional_hours() {
+        let account = |offset: f64| {
+            parse_account_balance(&json!({"currency": "THB", "timezone_offset_hours_utc": offset})).unwrap()
+        };
+
+        assert_eq!(account(7.0).utc_offset().local_minus_utc(), 7 * 3600);
+        assert_eq!(account(5.5).utc_offset().local_minus_utc(), 5 * 3600 + 1800);
+        assert_eq!(account(-4.0).utc_offset().local_minus_utc(), -4 * 3600);
+    }
+
+    #[test]
+    fn graph_errors_are_classified_by_code() {
+        let classify = |code, subcode, transient| FacebookApiError::from_graph(graph_error(code, subcode, transient));
+
+        assert!(matches!(classify(190, Some(463), false), FacebookApiError::TokenExpired(_)));
+        assert!(matches!(classify(190, None, false), FacebookApiError::InvalidToken(_)));
+        assert!(matches!(classify(102, None, false), FacebookApiError::InvalidToken(_)));
+        assert!(matches!(classify(200, None, false), FacebookApiError::PermissionDenied(_)));
+        assert!(matches!(classify(4, None, false), FacebookApiError::RateLimited(_)));
+        assert!(matches!(classify(80004, None, false), FacebookApiError::RateLimited(_)));
+        assert!(matches!(classify(2, None, false), FacebookApiError::TemporarilyUnavailable(_)));
+        assert!(matches!(classify(100, None, true), FacebookApiError::TemporarilyUnavailable(_)));
+        assert!(matches!(classify(100, None, false), FacebookApiError::InvalidParameter(_)));
+        assert!(matches!(classify(368, None, false), FacebookApiError::Graph(_)));
+    }
+
+    #[test]
+    fn only_token_errors_are_auth_errors() {
+        assert!(FacebookApiError::from_graph(graph_error(190, Some(463), false)).is_auth_error());
+        assert!(FacebookApiError::from_graph(graph_error(190, None, false)).is_auth_error());
+        assert!(!FacebookApiError::from_graph(graph_error(200, None, false)).is_auth_error());
+    }
+
+    #[test]
+    fn update_outcome_keeps_the_first_account_wide_error() {
+        let mut error = None;
+        let outcomes = [
+            update_outcome(Ok(json!({"success": true})), &mut error),
+            update_outcome(Err(FacebookApiError::from_graph(graph_error(100, None, false))), &mut error),
+            update_outcome(Err(FacebookApiError::from_graph(graph_error(17, None, false))), &mut error),
+            update_outcome(Err(FacebookApiError::from_graph(graph_error(190, None, false))), &mut error),
+        ];
+
+        assert!(matches!(outcomes[0], StatusUpdateOutcome::Updated));
+        assert!(outcomes[1..].iter().all(|outcome| matches!(outcome, StatusUpdateOutcome::Failed(_))));
+        assert!(matches!(error, Some(FacebookApiError::RateLimited(_))));
+    }
+}
@@ -1,7 +1,12 @@
 mod config;
 mod constants;
+mod currency;
 mod db;
 mod models;
+mod rate_limit;
+mod retry;
+mod scheduler;
+mod secret;
 mod telegram;
 mod facebook;
 mod worker;
@@ -10,27 +15,30 @@ use std::error::Error;
 use std::sync::Arc;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     // Load configuration
     let config = config::Config::from_env()?;
+    if let Some(app) = &config.graph.app {
+        println!("Signing Graph API requests with appsecret_proof for app {}", app.app_id);
+    }
     
-    // Initialize database connection
-    let db = Arc::new(tokio::sync::Mutex::new(db::Database::new(&config.database_url).await?));
-    
-    // Get Facebook accounts (now includes Telegram config)
-    let facebook_accounts = db.lock().await.get_facebook_accounts().await?;
+    // Initialize the database connection pool
+    println!("Connecting to the database with sslmode={}", config.database_tls.ssl_mode);
+    let db = Arc::new(
+        db::Database::new(&config.database_url, &config.database_pool, &config.database_tls).await?,
+    );
     
-    // Get thresholds from database
-    let thresholds = worker::AdMetricsThresholds::from_db(&db).await?;
-
     // Initialize worker
-    let worker = worker::AdWorker::new(
+    let worker = Arc::new(worker::AdWorker::new(
         db,
-        thresholds,
-    );
+        config.graph.clone(),
+        config.retry,
+        config.token,
+        config.scheduler,
+    ));
 
     // Run the worker
-    worker.run(facebook_accounts).await?;
+    worker.run().await?;
 
     Ok(())
 } 
\ No newline at end of file
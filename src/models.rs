@@ -1,31 +1,283 @@
+use crate::currency::Money;
+use crate::secret::SecretString;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FacebookAccount {
     pub id: i32,
-    pub access_token: String,
+    pub access_token: SecretString,
     pub account_id: String,
     pub is_active: bool,
     pub interval: i32,  // Monitoring interval in minutes
+    pub insights_window: InsightsWindow, // Window the ad insights are judged over
+    pub conversion_action_types: Vec<String>, // Actions counted as conversions
+    pub low_funds_threshold: Option<f64>, // Alert when remaining funds drop below this, in account currency
+    pub budget_scaling: Option<BudgetScalingRule>, // None when winners are not scaled automatically
+    pub last_run_at: Option<DateTime<Utc>>, // Start of the last monitoring run, for catch-up after downtime
     pub telegram_config: TelegramConfig, // Add telegram config to each account
 }
 
+/// Reporting window for ad insights. Presets and custom ranges are evaluated
+/// by Facebook in the ad account's own time zone.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InsightsWindow {
+    Today,
+    Yesterday,
+    Last3Days,
+    Last7Days,
+    #[default]
+    Last30Days, // Facebook's default when no window is requested
+    Lifetime,
+    TimeRange { since: NaiveDate, until: NaiveDate },
+}
+
+impl InsightsWindow {
+    /// The Graph API `date_preset` for this window, `None` for a custom range.
+    pub fn date_preset(&self) -> Option<&'static str> {
+        match self {
+            Self::Today => Some("today"),
+            Self::Yesterday => Some("yesterday"),
+            Self::Last3Days => Some("last_3d"),
+            Self::Last7Days => Some("last_7d"),
+            Self::Last30Days => Some("last_30d"),
+            Self::Lifetime => Some("maximum"),
+            Self::TimeRange { .. } => None,
+        }
+    }
+}
+
+impl FromStr for InsightsWindow {
+    type Err = String;
+
+    /// Parses the `facebook_accounts.insights_window` column: a preset name
+    /// (`today`, `yesterday`, `last_3d`, `last_7d`, `last_30d`, `lifetime`)
+    /// or a custom range written as `YYYY-MM-DD..YYYY-MM-DD`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "today" => Ok(Self::Today),
+            "yesterday" => Ok(Self::Yesterday),
+            "last_3d" => Ok(Self::Last3Days),
+            "last_7d" => Ok(Self::Last7Days),
+            "last_30d" => Ok(Self::Last30Days),
+            "lifetime" | "maximum" => Ok(Self::Lifetime),
+            range => {
+                let (since, until) = range
+                    .split_once("..")
+                    .ok_or_else(|| format!("unknown insights window: {}", range))?;
+                let since = NaiveDate::from_str(since).map_err(|e| e.to_string())?;
+                let until = NaiveDate::from_str(until).map_err(|e| e.to_string())?;
+                if since > until {
+                    return Err(format!("insights window starts after it ends: {}", range));
+                }
+                Ok(Self::TimeRange { since, until })
+            }
+        }
+    }
+}
+
+impl fmt::Display for InsightsWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lifetime => write!(f, "lifetime"),
+            Self::TimeRange { since, until } => write!(f, "{}..{}", since, until),
+            preset => write!(f, "{}", preset.date_preset().unwrap_or_default()),
+        }
+    }
+}
+
+/// Result of inspecting an access token with `/debug_token`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenInfo {
+    pub is_valid: bool,
+    pub token_type: Option<String>, // USER, SYSTEM_USER, PAGE, ...
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>, // None for tokens that never expire
+    pub data_access_expires_at: Option<DateTime<Utc>>,
+    pub error: Option<String>, // Why Facebook considers the token invalid
+}
+
+impl TokenInfo {
+    pub fn invalid(error: String) -> Self {
+        Self {
+            is_valid: false,
+            token_type: None,
+            scopes: Vec::new(),
+            expires_at: None,
+            data_access_expires_at: None,
+            error: Some(error),
+        }
+    }
+
+    /// The earlier of the token and data access expiry, if either is set.
+    pub fn next_expiry(&self) -> Option<DateTime<Utc>> {
+        [self.expires_at, self.data_access_expires_at]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+}
+
+/// Current access token of an account and its last recorded health. Read
+/// fresh every cycle, since the worker may have replaced the token.
+#[derive(Debug, Clone)]
+pub struct TokenStatus {
+    pub access_token: SecretString,
+    pub is_valid: Option<bool>, // None until the token has been inspected
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+/// A long-lived token obtained through `fb_exchange_token`.
+#[derive(Debug, Clone)]
+pub struct ExchangedToken {
+    pub access_token: SecretString,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Raises the daily budget of ad sets that keep beating a target cost per action.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BudgetScalingRule {
+    pub target_cost_per_action: f64,      // Every day of the window must come in below this
+    pub window_days: i32,                 // Complete days judged, ending yesterday
+    pub increase_pct: f64,                // Raise applied per cycle
+    pub max_daily_increase_pct: f64,      // Total raise allowed within one day of the account's time zone
+    pub max_daily_budget: Option<f64>,    // Ceiling for the daily budget, in account currency
+}
+
+/// A daily budget raise applied to an ad set, as logged in `budget_changes`.
+#[derive(Debug, Clone)]
+pub struct BudgetChange {
+    pub adset_id: String,
+    pub adset_name: String,
+    pub old_budget: Money,
+    pub new_budget: Money,
+    pub cost_per_action: f64, // Over the judged window
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TelegramConfig {
-    pub bot_token: String,
+    pub bot_token: SecretString,
     pub chat_id: i64,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AdAccountMetrics {
   pub account_id: String,
+  pub currency: String,
   pub spend: f64,
   pub impressions: i64,
   pub clicks: i64,
   pub conversions: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Effective thresholds for one ad, ad set or campaign, resolved from the
+/// `threshold_rules` that apply to it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub struct AdThresholds {
-    pub max_cost_per_action: f64,
+    pub max_cost_per_action: f64,    // Pause above this
+    pub resume_cost_per_action: f64, // Resume below this, at most `max_cost_per_action`
+    pub cooldown_hours: i64,         // Minimum time between two status changes of one object
+    pub control_level: ControlLevel, // Objects paused or resumed when the threshold is crossed
+    pub guards: DataGuards,
+}
+
+/// One row of `threshold_rules`. A rule without an account is global; one with
+/// a campaign or ad set applies only there. Unset fields are inherited from the
+/// next broader scope: ad set → campaign → account → global.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ThresholdRule {
+    pub account_id: Option<i32>,
+    pub campaign_id: Option<String>,
+    pub adset_id: Option<String>,
+    pub max_cost_per_action: Option<f64>,
+    pub resume_cost_per_action: Option<f64>,
+    pub cooldown_hours: Option<i64>,
+    pub control_level: Option<ControlLevel>,
+    pub min_spend: Option<f64>,
+    pub min_impressions: Option<i64>,
+    pub min_age_hours: Option<i64>,
+    pub min_conversions: Option<i64>,
+}
+
+impl ThresholdRule {
+    /// 0 for global rules up to 3 for ad set rules; narrower scopes win.
+    pub fn scope(&self) -> u8 {
+        if self.adset_id.is_some() {
+            3
+        } else if self.campaign_id.is_some() {
+            2
+        } else if self.account_id.is_some() {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// Minimum data an ad, ad set or campaign needs before the rule may pause or
+/// resume it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct DataGuards {
+    pub min_spend: f64,       // In account currency
+    pub min_impressions: i64,
+    pub min_age_hours: i64,   // Since the object was created
+    pub min_conversions: i64, // Below this only spend decides, and only towards pausing
+}
+
+impl Default for DataGuards {
+    fn default() -> Self {
+        Self {
+            min_spend: 0.0,
+            min_impressions: 0,
+            min_age_hours: 0,
+            min_conversions: 1,
+        }
+    }
+}
+
+/// Level of the ad account hierarchy a threshold rule acts on. Above ad level
+/// the rule is judged on the ad set's or campaign's own aggregated insights.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ControlLevel {
+    #[default]
+    Ad,
+    AdSet,
+    Campaign,
+}
+
+impl ControlLevel {
+    /// Capitalised name used in Telegram messages.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Ad => "Ad",
+            Self::AdSet => "Ad set",
+            Self::Campaign => "Campaign",
+        }
+    }
+}
+
+impl FromStr for ControlLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ad" => Ok(Self::Ad),
+            "adset" | "ad_set" => Ok(Self::AdSet),
+            "campaign" => Ok(Self::Campaign),
+            other => Err(format!("unknown control level: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for ControlLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ad => write!(f, "ad"),
+            Self::AdSet => write!(f, "adset"),
+            Self::Campaign => write!(f, "campaign"),
+        }
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,305 @@
+use crate::config::RateLimitConfig;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::time::{self, Duration, Instant};
+
+// Usage headers returned by the Graph API
+const APP_USAGE_HEADER: &str = "x-app-usage";
+const AD_ACCOUNT_USAGE_HEADER: &str = "x-ad-account-usage";
+const BUSINESS_USE_CASE_USAGE_HEADER: &str = "x-business-use-case-usage";
+
+/// Latest usage reported for an ad account, as percentages of the allowed quota.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AccountUsage {
+    pub app_pct: f64,
+    pub ad_account_pct: f64,
+    pub business_use_case_pct: f64,
+    pub regain_access_secs: u64, // From `estimated_time_to_regain_access` / `reset_time_duration`
+}
+
+impl AccountUsage {
+    pub fn max_pct(&self) -> f64 {
+        self.app_pct
+            .max(self.ad_account_pct)
+            .max(self.business_use_case_pct)
+    }
+}
+
+#[derive(Default)]
+struct AccountState {
+    usage: AccountUsage,
+    blocked_until: Option<Instant>,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    app_pct: f64,
+    app_blocked_until: Option<Instant>,
+    accounts: HashMap<String, AccountState>,
+}
+
+/// Tracks Marketing API usage across every account served by the same app and
+/// delays requests before Facebook starts throttling them.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(LimiterState::default()),
+        }
+    }
+
+    /// Current usage for `account_id`, if any response for it has been seen.
+    pub fn usage(&self, account_id: &str) -> Option<AccountUsage> {
+        let state = self.state.lock().unwrap();
+        state.accounts.get(account_id).map(|account| AccountUsage {
+            app_pct: state.app_pct,
+            ..account.usage
+        })
+    }
+
+    /// Waits until a request for `account_id` may be sent. When that would be
+    /// after `deadline`, returns the delay right away instead of sleeping.
+    pub async fn wait_turn(&self, account_id: &str, deadline: Option<Instant>) -> Result<(), Duration> {
+        let delay = self.delay_for(account_id);
+        if deadline.is_some_and(|deadline| Instant::now() + delay > deadline) {
+            return Err(delay);
+        }
+        if !delay.is_zero() {
+            println!(
+                "Rate limiter: delaying request for account {} by {:?}",
+                account_id, delay
+            );
+            time::sleep(delay).await;
+        }
+        Ok(())
+    }
+
+    /// True while requests for `account_id` are paused, for the account
+    /// itself or app-wide.
+    pub fn is_blocked(&self, account_id: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let account_blocked_until = state.accounts.get(account_id).and_then(|account| account.blocked_until);
+        [state.app_blocked_until, account_blocked_until]
+            .into_iter()
+            .flatten()
+            .any(|until| until > now)
+    }
+
+    fn delay_for(&self, account_id: &str) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        // The app quota is shared, so every account waits, as after a code 4
+        // throttle. The block stands in for the reading until fresh usage arrives
+        if state.app_pct >= self.config.pause_pct {
+            state.app_blocked_until = Some(now + Duration::from_secs(self.config.pause_secs));
+            state.app_pct = 0.0;
+        }
+
+        let app_pct = state.app_pct;
+        let app_blocked_until = state.app_blocked_until;
+        let account = state.accounts.entry(account_id.to_string()).or_default();
+
+        if account.usage.max_pct() >= self.config.pause_pct {
+            // Pause once, then let the next response report fresh usage
+            let pause = Duration::from_secs(account.usage.regain_access_secs.max(self.config.pause_secs));
+            account.blocked_until = Some(now + pause);
+            account.usage = AccountUsage::default();
+        }
+
+        let pct = account.usage.max_pct().max(app_pct);
+
+        let blocked = [app_blocked_until, account.blocked_until]
+            .into_iter()
+            .flatten()
+            .map(|until| until.saturating_duration_since(now))
+            .max()
+            .unwrap_or_default();
+
+        let throttle = if pct >= self.config.slowdown_pct {
+            let ratio = (pct - self.config.slowdown_pct)
+                / (self.config.pause_pct - self.config.slowdown_pct);
+            Duration::from_millis((self.config.max_delay_ms as f64 * ratio) as u64)
+        } else {
+            Duration::ZERO
+        };
+
+        blocked.max(throttle)
+    }
+
+    /// Records the usage headers of a Graph API response.
+    pub fn record(&self, account_id: &str, headers: &HeaderMap) {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(app) = parse_header(headers, APP_USAGE_HEADER) {
+            state.app_pct = max_of(&app, &["call_count", "total_time", "total_cputime"]);
+        }
+
+        let account = state.accounts.entry(account_id.to_string()).or_default();
+
+        if let Some(ad_account) = parse_header(headers, AD_ACCOUNT_USAGE_HEADER) {
+            account.usage.ad_account_pct = max_of(&ad_account, &["acc_id_util_pct"]);
+            account.usage.regain_access_secs = ad_account["reset_time_duration"].as_u64().unwrap_or(0);
+        }
+
+        // Keyed by business id, each holding one entry per use case type
+        if let Some(Value::Object(businesses)) = parse_header(headers, BUSINESS_USE_CASE_USAGE_HEADER) {
+            let entries: Vec<&Value> = businesses
+                .values()
+                .filter_map(Value::as_array)
+                .flatten()
+                .collect();
+
+            account.usage.business_use_case_pct = entries
+                .iter()
+                .map(|entry| max_of(entry, &["call_count", "total_time", "total_cputime"]))
+                .fold(0.0, f64::max);
+
+            let regain_minutes = entries
+                .iter()
+                .filter_map(|entry| entry["estimated_time_to_regain_access"].as_u64())
+                .max()
+                .unwrap_or(0);
+            account.usage.regain_access_secs = account.usage.regain_access_secs.max(regain_minutes * 60);
+        }
+    }
+
+    /// Pauses requests after Facebook reported a throttle with the given error code.
+    pub fn throttled(&self, account_id: &str, code: i64) {
+        let mut state = self.state.lock().unwrap();
+        let account = state.accounts.entry(account_id.to_string()).or_default();
+        let backoff = Duration::from_secs(account.usage.regain_access_secs.max(self.config.pause_secs));
+        let until = Instant::now() + backoff;
+        account.blocked_until = Some(until);
+
+        // Code 4 is the app-wide limit, so every account has to wait
+        if code == 4 {
+            state.app_blocked_until = Some(until);
+        }
+
+        eprintln!(
+            "Rate limited on account {} (code {}), backing off for {:?}",
+            account_id, code, backoff
+        );
+    }
+}
+
+fn parse_header(headers: &HeaderMap, name: &str) -> Option<Value> {
+    let value = headers.get(name)?.to_str().ok()?;
+    serde_json::from_str(value).ok()
+}
+
+fn max_of(value: &Value, fields: &[&str]) -> f64 {
+    fields
+        .iter()
+        .filter_map(|field| value.get(field).and_then(Value::as_f64))
+        .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            slowdown_pct: 75.0,
+            pause_pct: 95.0,
+            max_delay_ms: 10_000,
+            pause_secs: 300,
+        })
+    }
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn account_usage(pct: f64, reset_secs: u64) -> HeaderMap {
+        headers(
+            AD_ACCOUNT_USAGE_HEADER,
+            &format!(r#"{{"acc_id_util_pct": {}, "reset_time_duration": {}}}"#, pct, reset_secs),
+        )
+    }
+
+    /// The delay, rounded to whole seconds so time passing during the test doesn't matter.
+    fn delay_secs(limiter: &RateLimiter, account_id: &str) -> u64 {
+        limiter.delay_for(account_id).as_secs_f64().round() as u64
+    }
+
+    #[test]
+    fn no_delay_below_slowdown() {
+        let limiter = limiter();
+        limiter.record("1", &account_usage(74.0, 0));
+
+        assert_eq!(limiter.delay_for("1"), Duration::ZERO);
+        assert_eq!(limiter.delay_for("unseen"), Duration::ZERO);
+    }
+
+    #[test]
+    fn delay_grows_linearly_towards_pause() {
+        let limiter = limiter();
+
+        limiter.record("1", &account_usage(80.0, 0));
+        assert_eq!(limiter.delay_for("1"), Duration::from_millis(2_500));
+
+        limiter.record("1", &account_usage(85.0, 0));
+        assert_eq!(limiter.delay_for("1"), Duration::from_millis(5_000));
+
+        // Usage from the business use case header counts the same way
+        limiter.record("1", &headers(BUSINESS_USE_CASE_USAGE_HEADER, r#"{"123": [{"call_count": 90}]}"#));
+        assert_eq!(limiter.delay_for("1"), Duration::from_millis(7_500));
+    }
+
+    #[test]
+    fn account_pause_lasts_until_access_is_regained() {
+        let limiter = limiter();
+
+        limiter.record("1", &account_usage(96.0, 600));
+        assert_eq!(delay_secs(&limiter, "1"), 600);
+
+        limiter.record("2", &account_usage(95.0, 60));
+        assert_eq!(delay_secs(&limiter, "2"), 300);
+
+        // Only the account over its limit waits
+        assert_eq!(limiter.delay_for("3"), Duration::ZERO);
+    }
+
+    #[test]
+    fn app_usage_at_pause_blocks_every_account() {
+        let limiter = limiter();
+        limiter.record("1", &headers(APP_USAGE_HEADER, r#"{"call_count": 95, "total_time": 10}"#));
+
+        assert_eq!(delay_secs(&limiter, "2"), 300);
+        assert_eq!(delay_secs(&limiter, "1"), 300);
+        assert!(limiter.is_blocked("3"));
+    }
+
+    #[test]
+    fn app_throttle_blocks_every_account() {
+        let limiter = limiter();
+        limiter.throttled("1", 4);
+
+        assert_eq!(delay_secs(&limiter, "2"), 300);
+        assert!(limiter.is_blocked("2"));
+    }
+
+    #[test]
+    fn account_throttle_blocks_only_that_account() {
+        let limiter = limiter();
+        limiter.throttled("1", 17);
+
+        assert_eq!(delay_secs(&limiter, "1"), 300);
+        assert_eq!(limiter.delay_for("2"), Duration::ZERO);
+        assert!(!limiter.is_blocked("2"));
+    }
+}
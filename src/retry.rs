@@ -0,0 +1,58 @@
+use crate::config::RetryConfig;
+use rand::Rng;
+use std::fmt::Display;
+use std::future::Future;
+use tokio::time::{self, Duration};
+
+/// Errors that may go away when the same request is sent again.
+pub trait Retryable {
+    fn is_retryable(&self) -> bool;
+
+    /// Delay asked for by the server (e.g. Telegram's `retry_after`), used
+    /// instead of the computed backoff.
+    fn retry_after(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Runs `op` until it succeeds, fails with a non-retryable error, or
+/// `max_attempts` have been made, sleeping with exponential backoff in between.
+pub async fn with_retry<T, E, F, Fut>(config: &RetryConfig, label: &str, mut op: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Display,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Err(e) if attempt < config.max_attempts && e.is_retryable() => {
+                let delay = e.retry_after().unwrap_or_else(|| backoff(config, attempt));
+                eprintln!(
+                    "{} failed (attempt {}/{}), retrying in {:?}: {}",
+                    label, attempt, config.max_attempts, delay, e
+                );
+                time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Delay before retry number `attempt` (starting at 1), capped at `max_delay_ms`
+/// and spread by `jitter` so clients don't retry in lockstep.
+fn backoff(config: &RetryConfig, attempt: u32) -> Duration {
+    let exponential = config
+        .base_delay_ms
+        .saturating_mul(2u64.saturating_pow(attempt - 1))
+        .min(config.max_delay_ms);
+
+    let factor = if config.jitter > 0.0 {
+        rand::thread_rng().gen_range(1.0 - config.jitter..=1.0 + config.jitter)
+    } else {
+        1.0
+    };
+
+    Duration::from_millis((exponential as f64 * factor) as u64)
+}
@@ -0,0 +1,282 @@
+use crate::config::{CatchUp, SchedulerConfig};
+use crate::models::FacebookAccount;
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use std::collections::HashMap;
+
+struct Entry {
+    account_id: String,
+    interval: Duration,
+    last_run: Option<DateTime<Utc>>,
+    next_run: DateTime<Utc>,
+}
+
+/// Next-run times of every monitored account, each on its own interval.
+pub struct Scheduler {
+    config: SchedulerConfig,
+    entries: HashMap<i32, Entry>,
+}
+
+impl Scheduler {
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self {
+            config,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Schedules the account's first run after its `last_run_at`. Runs missed
+    /// while the worker was down are handled by the catch-up rule: at most one
+    /// run is made up for, never every missed slot.
+    pub fn add(&mut self, account: &FacebookAccount, now: DateTime<Utc>) {
+        let interval = self.interval(account);
+        let next_run = match account.last_run_at {
+            // Spread first runs over the jitter window so they don't fire together
+            None => now + self.spread(interval),
+            Some(last_run) if last_run + interval > now => last_run + interval + self.jitter(interval),
+            Some(last_run) => match self.config.catch_up {
+                CatchUp::RunOnce => now + self.spread(interval),
+                CatchUp::Skip => {
+                    let missed = (now - last_run).num_seconds() / interval.num_seconds();
+                    last_run + interval * (missed as i32 + 1) + self.jitter(interval)
+                }
+            },
+        };
+
+        println!("Account {} scheduled every {} min, next run at {}", account.account_id, interval.num_minutes(), next_run);
+        self.entries.insert(account.id, Entry {
+            account_id: account.account_id.clone(),
+            interval,
+            last_run: account.last_run_at,
+            next_run,
+        });
+    }
+
+    /// Brings the schedule in line with the accounts currently in the database:
+    /// new accounts are added, removed or deactivated ones dropped, and a changed
+    /// interval moves the next run to one new interval after the last run.
+    pub fn sync(&mut self, accounts: &[FacebookAccount], now: DateTime<Utc>) {
+        self.entries.retain(|id, entry| {
+            let active = accounts.iter().any(|account| account.id == *id);
+            if !active {
+                println!("Account {} removed from the schedule", entry.account_id);
+            }
+            active
+        });
+
+        for account in accounts {
+            let interval = self.interval(account);
+            let Some(entry) = self.entries.get(&account.id) else {
+                self.add(account, now);
+                continue;
+            };
+            if entry.interval == interval {
+                continue;
+            }
+
+            let next_run = entry.last_run.map_or(now, |last_run| last_run + interval).max(now) + self.spread(interval);
+            println!(
+                "Account {} rescheduled every {} min, next run at {}",
+                account.account_id, interval.num_minutes(), next_run
+            );
+            if let Some(entry) = self.entries.get_mut(&account.id) {
+                entry.interval = interval;
+                entry.next_run = next_run;
+            }
+        }
+    }
+
+    /// IDs of the accounts whose next run is due, earliest first.
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<i32> {
+        let mut due: Vec<(&i32, &Entry)> = self.entries.iter().filter(|(_, entry)| entry.next_run <= now).collect();
+        due.sort_by_key(|(_, entry)| entry.next_run);
+        due.into_iter().map(|(id, _)| *id).collect()
+    }
+
+    /// Schedules the next run one interval after the run that started at
+    /// `started_at`. A run that overran its interval is followed right away;
+    /// an account removed while it was running is left unscheduled.
+    pub fn completed(&mut self, id: i32, started_at: DateTime<Utc>, now: DateTime<Utc>) {
+        let Some(interval) = self.entries.get(&id).map(|entry| entry.interval) else {
+            return;
+        };
+        let next_run = (started_at + interval + self.jitter(interval)).max(now);
+        if let Some(entry) = self.entries.get_mut(&id) {
+            entry.last_run = Some(started_at);
+            entry.next_run = next_run;
+        }
+    }
+
+    /// Time until the earliest next run of an account not in `running`, zero
+    /// when one is already due.
+    pub fn time_until_next(&self, now: DateTime<Utc>, running: &[i32]) -> Option<std::time::Duration> {
+        self.entries
+            .iter()
+            .filter(|(id, _)| !running.contains(id))
+            .map(|(_, entry)| entry.next_run)
+            .min()
+            .map(|next_run| (next_run - now).to_std().unwrap_or_default())
+    }
+
+    fn interval(&self, account: &FacebookAccount) -> Duration {
+        if account.interval > 0 {
+            Duration::minutes(account.interval as i64)
+        } else {
+            Duration::seconds(self.config.default_interval_secs as i64)
+        }
+    }
+
+    /// Random offset of up to `jitter` of the interval either way.
+    fn jitter(&self, interval: Duration) -> Duration {
+        let max = (interval.num_milliseconds() as f64 * self.config.jitter) as i64;
+        if max == 0 {
+            return Duration::zero();
+        }
+        Duration::milliseconds(rand::thread_rng().gen_range(-max..=max))
+    }
+
+    /// Random delay of up to `jitter` of the interval.
+    fn spread(&self, interval: Duration) -> Duration {
+        self.jitter(interval).abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{InsightsWindow, TelegramConfig};
+    use crate::secret::SecretString;
+
+    fn scheduler(catch_up: CatchUp) -> Scheduler {
+        Scheduler::new(SchedulerConfig {
+            jitter: 0.0,
+            catch_up,
+            ..SchedulerConfig::default()
+        })
+    }
+
+    fn account(id: i32, interval: i32, last_run_at: Option<DateTime<Utc>>) -> FacebookAccount {
+        FacebookAccount {
+            id,
+            access_token: SecretString::new(String::new()),
+            account_id: id.to_string(),
+            is_active: true,
+            interval,
+            insights_window: InsightsWindow::default(),
+            conversion_action_types: Vec::new(),
+            low_funds_threshold: None,
+            budget_scaling: None,
+            last_run_at,
+            telegram_config: TelegramConfig {
+                bot_token: SecretString::new(String::new()),
+                chat_id: 0,
+            },
+        }
+    }
+
+    fn next_run(scheduler: &Scheduler, id: i32) -> DateTime<Utc> {
+        scheduler.entries[&id].next_run
+    }
+
+    #[test]
+    fn new_account_runs_right_away() {
+        let now = Utc::now();
+        let mut scheduler = scheduler(CatchUp::RunOnce);
+        scheduler.add(&account(1, 30, None), now);
+
+        assert_eq!(scheduler.due(now), vec![1]);
+    }
+
+    #[test]
+    fn account_within_its_interval_waits_for_it() {
+        let now = Utc::now();
+        let last_run = now - Duration::minutes(10);
+        let mut scheduler = scheduler(CatchUp::RunOnce);
+        scheduler.add(&account(1, 30, Some(last_run)), now);
+
+        assert_eq!(next_run(&scheduler, 1), last_run + Duration::minutes(30));
+        assert!(scheduler.due(now).is_empty());
+    }
+
+    #[test]
+    fn run_once_makes_up_for_missed_runs_immediately() {
+        let now = Utc::now();
+        let mut scheduler = scheduler(CatchUp::RunOnce);
+        scheduler.add(&account(1, 30, Some(now - Duration::minutes(95))), now);
+
+        assert_eq!(next_run(&scheduler, 1), now);
+    }
+
+    #[test]
+    fn skip_waits_for_the_next_regular_slot() {
+        let now = Utc::now();
+        let last_run = now - Duration::minutes(95);
+        let mut scheduler = scheduler(CatchUp::Skip);
+        scheduler.add(&account(1, 30, Some(last_run)), now);
+
+        // Slots at +30, +60 and +90 were missed; +120 is 25 minutes from now
+        assert_eq!(next_run(&scheduler, 1), last_run + Duration::minutes(120));
+        assert_eq!(next_run(&scheduler, 1), now + Duration::minutes(25));
+    }
+
+    #[test]
+    fn skip_on_an_exact_slot_boundary_moves_to_the_following_one() {
+        let now = Utc::now();
+        let last_run = now - Duration::minutes(60);
+        let mut scheduler = scheduler(CatchUp::Skip);
+        scheduler.add(&account(1, 30, Some(last_run)), now);
+
+        assert_eq!(next_run(&scheduler, 1), now + Duration::minutes(30));
+    }
+
+    #[test]
+    fn unset_interval_uses_the_default() {
+        let now = Utc::now();
+        let last_run = now - Duration::minutes(1);
+        let mut scheduler = scheduler(CatchUp::RunOnce);
+        scheduler.add(&account(1, 0, Some(last_run)), now);
+
+        let default = Duration::seconds(SchedulerConfig::default().default_interval_secs as i64);
+        assert_eq!(next_run(&scheduler, 1), last_run + default);
+    }
+
+    #[test]
+    fn completed_run_schedules_one_interval_after_its_start() {
+        let now = Utc::now();
+        let mut scheduler = scheduler(CatchUp::RunOnce);
+        scheduler.add(&account(1, 30, None), now);
+
+        scheduler.completed(1, now, now + Duration::minutes(5));
+        assert_eq!(next_run(&scheduler, 1), now + Duration::minutes(30));
+
+        // A run longer than its interval is followed right away
+        scheduler.completed(1, now, now + Duration::minutes(45));
+        assert_eq!(next_run(&scheduler, 1), now + Duration::minutes(45));
+    }
+
+    #[test]
+    fn sync_adds_removes_and_reschedules() {
+        let now = Utc::now();
+        let last_run = now - Duration::minutes(10);
+        let mut scheduler = scheduler(CatchUp::RunOnce);
+        scheduler.add(&account(1, 30, Some(last_run)), now);
+        scheduler.add(&account(2, 30, Some(last_run)), now);
+
+        scheduler.sync(&[account(1, 60, Some(last_run)), account(3, 30, None)], now);
+
+        assert_eq!(next_run(&scheduler, 1), last_run + Duration::minutes(60));
+        assert!(!scheduler.entries.contains_key(&2));
+        assert_eq!(scheduler.due(now), vec![3]);
+    }
+
+    #[test]
+    fn time_until_next_ignores_running_accounts() {
+        let now = Utc::now();
+        let mut scheduler = scheduler(CatchUp::RunOnce);
+        scheduler.add(&account(1, 30, None), now);
+        scheduler.add(&account(2, 30, Some(now - Duration::minutes(20))), now);
+
+        assert_eq!(scheduler.time_until_next(now, &[]), Some(std::time::Duration::ZERO));
+        assert_eq!(scheduler.time_until_next(now, &[1]), Some(std::time::Duration::from_secs(600)));
+    }
+}
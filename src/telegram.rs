@@ -1,8 +1,12 @@
 use crate::models::{AdAccountMetrics, TelegramConfig};
+use crate::config::RetryConfig;
+use crate::currency::Money;
+use crate::retry::{with_retry, Retryable};
+use crate::secret::SecretString;
 use reqwest::Client;
-use serde_json::json;
-use std::error::Error;
+use serde_json::{json, Value};
 use thiserror::Error;
+use tokio::time::Duration;
 use crate::constants::{
     TELEGRAM_BASE_URL, 
     CONTENT_TYPE_HEADER, 
@@ -14,26 +18,57 @@ pub enum TelegramError {
     #[error("Failed to send message: {0}")]
     SendError(String),
     #[error("API request failed: {0}")]
-    RequestError(#[from] reqwest::Error),
+    RequestError(reqwest::Error), // URL stripped, it embeds the bot token
+    #[error("Rate limited, retry after {0}s")]
+    RateLimited(u64),
+    #[error("Server error {0}: {1}")]
+    ServerError(u16, String),
+}
+
+impl Retryable for TelegramError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::RequestError(e) => e.is_timeout() || e.is_connect(),
+            Self::RateLimited(_) | Self::ServerError(..) => true,
+            Self::SendError(_) => false,
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited(secs) => Some(Duration::from_secs(*secs)),
+            _ => None,
+        }
+    }
 }
 
 pub struct TelegramNotifier {
     client: Client,
-    base_url: String,
+    bot_token: SecretString,
     chat_id: i64,
+    retry: RetryConfig,
 }
 
 impl TelegramNotifier {
-    pub fn new(config: TelegramConfig) -> Self {
+    pub fn new(config: TelegramConfig, retry: RetryConfig) -> Self {
         Self {
             client: Client::new(),
-            base_url: format!("{}/bot{}", TELEGRAM_BASE_URL, config.bot_token),
+            bot_token: config.bot_token,
             chat_id: config.chat_id,
+            retry,
         }
     }
 
     pub async fn send_message(&self, message: &str) -> Result<(), TelegramError> {
-        let url = format!("{}/sendMessage", self.base_url);
+        with_retry(&self.retry, "Telegram sendMessage", || self.send_message_once(message)).await?;
+
+        println!("Telegram message sent successfully");
+        Ok(())
+    }
+
+    async fn send_message_once(&self, message: &str) -> Result<(), TelegramError> {
+        // Telegram only accepts the bot token in the URL path
+        let url = format!("{}/bot{}/sendMessage", TELEGRAM_BASE_URL, self.bot_token.expose());
         
         let params = json!({
             "chat_id": self.chat_id,
@@ -47,28 +82,45 @@ impl TelegramNotifier {
             .json(&params)
             .send()
             .await
-            .map_err(|e| TelegramError::RequestError(e))?;
+            .map_err(|e| TelegramError::RequestError(e.without_url()))?;
 
-        if !response.status().is_success() {
+        let status = response.status();
+        if !status.is_success() {
             let error_text = response.text().await
+                .map(|text| self.bot_token.redact(&text))
                 .unwrap_or_else(|_| "Unknown error".to_string());
+
+            if status.as_u16() == 429 {
+                let retry_after = serde_json::from_str::<Value>(&error_text)
+                    .ok()
+                    .and_then(|body| body["parameters"]["retry_after"].as_u64())
+                    .unwrap_or(1);
+                return Err(TelegramError::RateLimited(retry_after));
+            }
+            if status.is_server_error() {
+                return Err(TelegramError::ServerError(status.as_u16(), error_text));
+            }
             return Err(TelegramError::SendError(error_text));
         }
 
-        println!("Telegram message sent successfully");
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub async fn send_metrics_alert(&self, metrics: &AdAccountMetrics) -> Result<(), TelegramError> {
         let message = format!(
             "📊 Ad Account Update: {}\n\
-             💰 Spend: ${:.2}\n\
+             💰 Spend: {}\n\
              👁 Impressions: {}\n\
              🖱 Clicks: {}\n\
              ✅ Conversions: {}\n",
-            metrics.account_id, metrics.spend, metrics.impressions, metrics.clicks, metrics.conversions
+            metrics.account_id,
+            Money::new(metrics.spend, &metrics.currency),
+            metrics.impressions,
+            metrics.clicks,
+            metrics.conversions
         );
 
         self.send_message(&message).await
     }
-} 
\ No newline at end of file
+}
@@ -1,54 +1,114 @@
-use crate::facebook::{FacebookAPI, AdData};
-use crate::models::{FacebookAccount, AdThresholds};
+use crate::facebook::{FacebookAPI, FacebookApiError, GraphError, AccountBalance, AccountSnapshot, AdData, AdObject, AdSet, StatusUpdate, StatusUpdateOutcome};
+use crate::models::{AdThresholds, BudgetChange, BudgetScalingRule, ControlLevel, DataGuards, FacebookAccount, ThresholdRule, TokenInfo};
+use crate::config::{GraphApiConfig, RetryConfig, SchedulerConfig, TokenCheckConfig};
+use crate::rate_limit::RateLimiter;
+use crate::scheduler::Scheduler;
 use crate::telegram::TelegramNotifier;
 use crate::db::Database;
-use crate::constants::WORKER_INTERVAL_SECS;
+use crate::currency::Money;
+use crate::secret::SecretString;
+use crate::constants::THRESHOLD_COOLDOWN_HOURS;
 use std::error::Error;
 use std::sync::Arc;
-use tokio::time::{self, Duration};
-
-pub const WORKER_INTERVAL: Duration = Duration::from_secs(WORKER_INTERVAL_SECS);
+use chrono::{DateTime, Duration as ChronoDuration, NaiveTime, Utc};
+use std::collections::HashMap;
+use tokio::task::{self, JoinSet};
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
 
+/// Threshold rules that apply to one account, re-read every cycle.
 #[derive(Debug, Clone)]
 pub struct AdMetricsThresholds {
-    pub max_cost_per_action: f64,
+    rules: Vec<ThresholdRule>,
 }
 
 impl AdMetricsThresholds {
-  pub async fn from_db(db: &Arc<tokio::sync::Mutex<Database>>) -> Result<Self, Box<dyn Error>> {
-      let thresholds = db.lock().await.get_ad_thresholds().await?;
-      Ok(Self {
-          max_cost_per_action: thresholds.max_cost_per_action,
+  pub async fn from_db(db: &Database, account_id: i32) -> Result<Self, Box<dyn Error + Send + Sync>> {
+      let rules = db.get_threshold_rules(account_id).await?;
+      Ok(Self { rules })
+  }
+
+  /// Effective thresholds for an object in `campaign_id` (and `adset_id`, for
+  /// ads and ad sets). Each setting comes from the narrowest rule that sets
+  /// it; `None` when no rule sets a maximum cost per action.
+  pub fn resolve(&self, campaign_id: &str, adset_id: Option<&str>) -> Option<AdThresholds> {
+      let mut rules: Vec<&ThresholdRule> = self
+          .rules
+          .iter()
+          .filter(|rule| {
+              rule.campaign_id.as_deref().is_none_or(|id| id == campaign_id)
+                  && rule.adset_id.as_deref().is_none_or(|id| Some(id) == adset_id)
+          })
+          .collect();
+      rules.sort_by_key(|rule| std::cmp::Reverse(rule.scope()));
+
+      let max_cost_per_action = inherit(&rules, |rule| rule.max_cost_per_action)?;
+      let defaults = DataGuards::default();
+
+      Some(AdThresholds {
+          max_cost_per_action,
+          // Resuming above the pause threshold would flap every cycle
+          resume_cost_per_action: inherit(&rules, |rule| rule.resume_cost_per_action)
+              .map_or(max_cost_per_action, |resume| resume.min(max_cost_per_action)),
+          cooldown_hours: inherit(&rules, |rule| rule.cooldown_hours).unwrap_or(THRESHOLD_COOLDOWN_HOURS),
+          control_level: inherit(&rules, |rule| rule.control_level).unwrap_or_default(),
+          guards: DataGuards {
+              min_spend: inherit(&rules, |rule| rule.min_spend).unwrap_or(defaults.min_spend),
+              min_impressions: inherit(&rules, |rule| rule.min_impressions).unwrap_or(defaults.min_impressions),
+              min_age_hours: inherit(&rules, |rule| rule.min_age_hours).unwrap_or(defaults.min_age_hours),
+              min_conversions: inherit(&rules, |rule| rule.min_conversions).unwrap_or(defaults.min_conversions),
+          },
       })
   }
 }
 
+/// First value set by `rules`, which are ordered narrowest scope first.
+fn inherit<T>(rules: &[&ThresholdRule], field: impl Fn(&ThresholdRule) -> Option<T>) -> Option<T> {
+    rules.iter().find_map(|rule| field(rule))
+}
+
 pub struct AdWorker {
-    db: Arc<tokio::sync::Mutex<Database>>,
-    thresholds: AdMetricsThresholds,
+    db: Arc<Database>,
+    graph_config: GraphApiConfig,
+    rate_limiter: Arc<RateLimiter>,
+    retry: RetryConfig,
+    token_check: TokenCheckConfig,
+    schedule: SchedulerConfig,
 }
 
 impl AdWorker {
     pub fn new(
-        db: Arc<tokio::sync::Mutex<Database>>,
-        thresholds: AdMetricsThresholds,
+        db: Arc<Database>,
+        graph_config: GraphApiConfig,
+        retry: RetryConfig,
+        token_check: TokenCheckConfig,
+        schedule: SchedulerConfig,
     ) -> Self {
         Self {
             db,
-            thresholds,
+            rate_limiter: Arc::new(RateLimiter::new(graph_config.rate_limit)),
+            graph_config,
+            retry,
+            token_check,
+            schedule,
         }
     }
 
-    pub async fn process_account(&self, account: &FacebookAccount) -> Result<(), Box<dyn Error>> {
+    /// Runs one monitoring cycle for the account. Requests the rate limiter
+    /// would hold past `deadline` fail as `RateLimited`.
+    pub async fn process_account(&self, account: &FacebookAccount, deadline: Instant) -> Result<(), Box<dyn Error + Send + Sync>> {
         // Create Telegram notifier for this specific account
-        let telegram = TelegramNotifier::new(account.telegram_config.clone());
-        
-        let fb_api = FacebookAPI::new(account.access_token.clone(), account.account_id.clone());
+        let telegram = TelegramNotifier::new(account.telegram_config.clone(), self.retry);
         
-        // Get ads for the account
-        let ads = fb_api.get_ads().await?;
+        let fb_api = match self.check_token(account, &telegram, deadline).await? {
+            Some(fb_api) => fb_api,
+            None => return Ok(()),
+        };
+
+        // Get campaigns, ad sets, ads and the account balance in one batch call
+        let snapshot = fb_api.get_account_snapshot(&account.insights_window).await?;
+        let balance = &snapshot.balance;
         
-        if ads.is_empty() {
+        if snapshot.ads.is_empty() {
             telegram.send_message(&format!(
                 "🔍 Account {} has no active ads", 
                 account.account_id
@@ -56,19 +116,117 @@ impl AdWorker {
             return Ok(());
         }
 
-        // Check account balance
-        let balance = fb_api.get_ad_account_balance().await?;
-        telegram.send_message(&format!(
-            "💰 Account balance {}: {}", 
-            account.account_id, 
-            balance.available_funds
-        )).await?;
+        // Report account balance
+        telegram.send_message(&balance_message(&account.account_id, balance)).await?;
+
+        // Only announce the drop below the threshold, not every cycle spent below it
+        match (account.low_funds_threshold, balance.remaining_funds()) {
+            (Some(threshold), Some(remaining)) if remaining.amount < threshold => {
+                if self.db.mark_low_funds(account.id).await? {
+                    telegram.send_message(&format!(
+                        "⚠️ Low funds in account {}: {} left, below {}",
+                        account.account_id,
+                        remaining,
+                        Money::new(threshold, &balance.currency)
+                    )).await?;
+                }
+            }
+            _ => self.db.clear_low_funds(account.id).await?,
+        }
 
         let mut all_paused = true;
-        let mut messages = Vec::new();
+        let mut messages = vec![format!(
+            "📅 Insights window: {} ({})",
+            account.insights_window, balance.timezone
+        )];
+
+        // Rules may have changed since the last cycle
+        let thresholds = AdMetricsThresholds::from_db(&self.db, account.id).await?;
+
+        for ad in &snapshot.ads {
+            let ad_thresholds = thresholds.resolve(&ad.campaign_id, Some(&ad.adset_id));
+            self.process_ad(account, &snapshot, ad, ad_thresholds.as_ref(), &mut all_paused, &mut messages);
+        }
+
+        // Each object is judged when the rules resolved for it act on its level
+        let mut objects: Vec<(&dyn AdObject, AdThresholds)> = Vec::new();
+        for ad in snapshot.ads.iter().filter(|ad| ad.effective_status != "DISAPPROVED") {
+            if let Some(ad_thresholds) = thresholds.resolve(&ad.campaign_id, Some(&ad.adset_id)) {
+                objects.push((ad, ad_thresholds));
+            }
+        }
+        for ad_set in &snapshot.ad_sets {
+            if let Some(ad_set_thresholds) = thresholds.resolve(&ad_set.campaign_id, Some(&ad_set.id)) {
+                objects.push((ad_set, ad_set_thresholds));
+            }
+        }
+        for campaign in &snapshot.campaigns {
+            if let Some(campaign_thresholds) = thresholds.resolve(&campaign.id, None) {
+                objects.push((campaign, campaign_thresholds));
+            }
+        }
+        objects.retain(|(object, object_thresholds)| object_thresholds.control_level == object.level());
+
+        // Objects changed within their cooldown are left alone
+        let longest_cooldown = objects.iter().map(|(_, t)| t.cooldown_hours).max().unwrap_or(0);
+        let recent_changes = self
+            .db
+            .get_recent_status_changes(account.id, Utc::now() - ChronoDuration::hours(longest_cooldown))
+            .await?;
+
+        let mut changes = Vec::new();
+
+        for (object, object_thresholds) in objects {
+            let cost_per_action = judged_cost(object, &object_thresholds, &account.conversion_action_types);
+            if object.level() != ControlLevel::Ad {
+                messages.push(status_message(object, object.status() == "PAUSED", cost_per_action, &balance.currency));
+            }
+            let Some(status) = decide_status(object, &object_thresholds, cost_per_action) else {
+                continue;
+            };
+            let cooling_down = |at: &&DateTime<Utc>| in_cooldown(**at, &object_thresholds, Utc::now());
+            if let Some(changed_at) = recent_changes.get(object.id()).filter(cooling_down) {
+                println!(
+                    "Skipping {} {} in account {}: changed at {}, cooling down until {}",
+                    object.level(),
+                    object.id(),
+                    account.account_id,
+                    changed_at,
+                    *changed_at + ChronoDuration::hours(object_thresholds.cooldown_hours)
+                );
+                continue;
+            }
+            changes.push((object, status));
+        }
+
+        // Apply status changes and report what Facebook actually did
+        let report = fb_api.update_statuses(&changes).await?;
+        for update in &report.updates {
+            if matches!(update.outcome, StatusUpdateOutcome::Updated) {
+                self.db.record_status_change(
+                    account.id,
+                    update.level,
+                    &update.id,
+                    &update.name,
+                    &update.status,
+                ).await?;
+            }
+            messages.push(status_update_message(update));
+        }
 
-        for ad in ads {
-            self.process_ad(&fb_api, &ad, &mut all_paused, &mut messages).await?;
+        // Scale winners, leaving alone anything paused or resumed this cycle
+        let result = match (report.error, &account.budget_scaling) {
+            (Some(e), _) => Err(e.into()),
+            (None, Some(rule)) => {
+                let changed: Vec<&str> = changes.iter().map(|(object, _)| object.id()).collect();
+                self.scale_budgets(account, rule, &fb_api, &snapshot, &changed, &mut messages).await
+            }
+            (None, None) => Ok(()),
+        };
+        if let Err(e) = result {
+            // Changes applied before an account-wide error still get reported
+            telegram.send_message(&messages.join("\n")).await?;
+            return Err(e);
         }
 
         if all_paused {
@@ -79,89 +237,748 @@ impl AdWorker {
         let combined_message = messages.join("\n");
         telegram.send_message(&combined_message).await?;
 
+        if let Some(usage) = fb_api.usage() {
+            println!(
+                "API usage for account {}: {:.0}% (app {:.0}%, account {:.0}%, business {:.0}%)",
+                account.account_id,
+                usage.max_pct(),
+                usage.app_pct,
+                usage.ad_account_pct,
+                usage.business_use_case_pct
+            );
+        }
+
         Ok(())
     }
 
-    async fn process_ad(
+    fn facebook_api(&self, account: &FacebookAccount, access_token: SecretString, deadline: Instant) -> FacebookAPI {
+        FacebookAPI::new(
+            access_token,
+            account.account_id.clone(),
+            &self.graph_config,
+            self.rate_limiter.clone(),
+        )
+        .with_deadline(deadline)
+    }
+
+    /// Loads the account's current access token, re-inspects it when due,
+    /// refreshes it ahead of expiry and warns when it cannot be refreshed.
+    /// Returns the client to use for this cycle, or `None` when the account
+    /// must not be polled.
+    async fn check_token(
         &self,
-        fb_api: &FacebookAPI,
+        account: &FacebookAccount,
+        telegram: &TelegramNotifier,
+        deadline: Instant,
+    ) -> Result<Option<FacebookAPI>, Box<dyn Error + Send + Sync>> {
+        let status = self.db.get_token_status(account.id).await?;
+        let mut fb_api = self.facebook_api(account, status.access_token, deadline);
+
+        // Invalid tokens are re-checked every cycle so a reconnected one is picked up
+        let due = status.is_valid != Some(true)
+            || status.checked_at.is_none_or(|checked_at| {
+                Utc::now() - checked_at >= ChronoDuration::hours(self.token_check.check_interval_hours)
+            });
+        if !due {
+            return Ok(Some(fb_api));
+        }
+
+        let mut info = fb_api.debug_token().await?;
+        self.db.update_token_status(account.id, &info).await?;
+
+        if !info.is_valid {
+            // Only announce the transition, not every skipped cycle
+            if status.is_valid != Some(false) {
+                telegram.send_message(&format!(
+                    "🔑 Access token for account {} is invalid: {}\nMonitoring is paused until the account is reconnected.",
+                    account.account_id,
+                    info.error.as_deref().unwrap_or("no reason given")
+                )).await?;
+            }
+            return Ok(None);
+        }
+
+        // Only user tokens can be exchanged; system user tokens are managed in Business Manager.
+        // Exchanging does not extend data access, so only the token's own expiry counts here.
+        let refreshable = info.token_type.as_deref() == Some("USER") && self.graph_config.app.is_some();
+        let refresh_due = info.expires_at.is_some_and(|expires_at| {
+            expires_at - Utc::now() <= ChronoDuration::days(self.token_check.refresh_before_days)
+        });
+        if refreshable && refresh_due {
+            match fb_api.exchange_token().await {
+                Ok(token) => {
+                    self.db.update_access_token(account.id, &token).await?;
+                    println!(
+                        "Refreshed access token for account {}, now expires {}",
+                        account.account_id,
+                        token.expires_at.map_or("never".to_string(), |at| at.to_string())
+                    );
+                    // The exchange may not push the expiry out, in which case
+                    // the warning below still applies to the new token
+                    info.expires_at = info.expires_at.max(token.expires_at);
+                    fb_api = self.facebook_api(account, token.access_token, deadline);
+                }
+                Err(e) => eprintln!("Failed to refresh access token for account {}: {}", account.account_id, e),
+            }
+        }
+
+        let Some(expiry) = info.next_expiry() else {
+            return Ok(Some(fb_api));
+        };
+        if expiry - Utc::now() <= ChronoDuration::days(self.token_check.warning_days) {
+            telegram.send_message(&format!(
+                "⏳ Access token for account {} expires on {}, please reconnect it before then",
+                account.account_id,
+                expiry.format("%Y-%m-%d %H:%M UTC")
+            )).await?;
+        }
+
+        Ok(Some(fb_api))
+    }
+
+    /// Reports the ad's state, taking a paused ad set or campaign into account.
+    fn process_ad(
+        &self,
+        account: &FacebookAccount,
+        snapshot: &AccountSnapshot,
         ad: &AdData,
+        thresholds: Option<&AdThresholds>,
         all_paused: &mut bool,
         messages: &mut Vec<String>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) {
         if ad.effective_status == "DISAPPROVED" {
             messages.push(format!("❌ Ad disapproved: {} waiting for deletion", ad.name));
-            return Ok(());
+            return;
         }
 
-        if ad.effective_status != "CAMPAIGN_PAUSED" {
+        let paused = snapshot.is_paused(ad);
+        if !paused {
             *all_paused = false;
         }
 
-        // Process cost per action
-        let cost_per_action = self.get_cost_per_action(ad);
-        
-        // Determine if ad status needs to change
-        let new_status = if self.should_close_ad(ad, cost_per_action) {
-            Some("PAUSED")
-        } else if self.should_open_ad(ad, cost_per_action) {
-            Some("ACTIVE")
-        } else {
-            None
+        // Without a rule there are no guards, so show the raw figure
+        let cost_per_action = match thresholds {
+            Some(thresholds) => judged_cost(ad, thresholds, &account.conversion_action_types),
+            None => ad.performance.cost_per_action(&account.conversion_action_types),
         };
+        messages.push(status_message(ad, paused, cost_per_action, &snapshot.balance.currency));
+    }
+
+    /// Raises the daily budget of active ad sets whose cost per action stayed
+    /// below the rule's target on every day of its window, within the per-day
+    /// and total caps. Returns the Telegram lines announcing each raise.
+    async fn scale_budgets(
+        &self,
+        account: &FacebookAccount,
+        rule: &BudgetScalingRule,
+        fb_api: &FacebookAPI,
+        snapshot: &AccountSnapshot,
+        changed: &[&str],
+        messages: &mut Vec<String>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let action_types = &account.conversion_action_types;
+        let below_target = |cost: Option<f64>| cost.is_some_and(|cost| cost < rule.target_cost_per_action);
 
-        // Update ad status if needed
-        if let Some(status) = new_status {
-            fb_api.update_ad_status(&ad.name, status).await?;
-            messages.push(format!("🧠 Updated ad status: {} to {}", ad.name, status));
+        // Ad sets under campaign budget optimization have no daily budget of their own
+        let candidates: Vec<&AdSet> = snapshot
+            .ad_sets
+            .iter()
+            .filter(|ad_set| {
+                ad_set.effective_status == "ACTIVE"
+                    && ad_set.daily_budget.is_some()
+                    && !changed.contains(&ad_set.id.as_str())
+                    && below_target(ad_set.performance.cost_per_action(action_types))
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Ok(());
         }
 
-        // Add status message
-        let status_msg = if ad.status == "PAUSED" || ad.effective_status == "CAMPAIGN_PAUSED" {
-            format!("❌ Ad paused: {}:💰{}", ad.name, cost_per_action)
-        } else {
-            format!("🟢 Ad active: {}:💰{}", ad.name, cost_per_action)
-        };
-        messages.push(status_msg);
+        // Judge complete days only, ending yesterday in the account's time zone
+        let window_days = rule.window_days.max(1);
+        let offset = snapshot.balance.utc_offset();
+        let today = Utc::now().with_timezone(&offset).date_naive();
+        let ids: Vec<&str> = candidates.iter().map(|ad_set| ad_set.id.as_str()).collect();
+        let daily = fb_api
+            .get_daily_performance(&ids, today - ChronoDuration::days(window_days as i64), today - ChronoDuration::days(1))
+            .await?;
 
-        Ok(())
-    }
+        // The daily cap resets when the account's day starts, not at UTC midnight
+        let day_start = (today.and_time(NaiveTime::MIN) - ChronoDuration::seconds(offset.local_minus_utc() as i64)).and_utc();
+        let mut raises = Vec::new();
+        let mut window_costs = Vec::new();
 
-    fn should_close_ad(&self, ad: &AdData, cost_per_action: f64) -> bool {
-        ad.status == "ACTIVE" 
-            && ad.effective_status == "ACTIVE" 
-            && cost_per_action > self.thresholds.max_cost_per_action
-    }
+        for (ad_set, days) in candidates.into_iter().zip(daily) {
+            let days = match days {
+                Ok(days) => days,
+                Err(e) => {
+                    eprintln!("Failed to read daily insights for ad set {}: {}", ad_set.id, e);
+                    continue;
+                }
+            };
+            // Days without delivery are missing from the result and break the streak
+            let costs: Vec<Option<f64>> = days.iter().map(|day| day.cost_per_action(action_types)).collect();
+            if costs.len() != window_days as usize || !costs.iter().all(|cost| below_target(*cost)) {
+                continue;
+            }
 
-    fn should_open_ad(&self, ad: &AdData, cost_per_action: f64) -> bool {
-        ad.status == "PAUSED" 
-            && ad.effective_status == "PAUSED" 
-            && cost_per_action < self.thresholds.max_cost_per_action
-    }
+            let Some(current) = &ad_set.daily_budget else {
+                continue;
+            };
+            let day_start_budget = self
+                .db
+                .get_budget_before(account.id, &ad_set.id, day_start)
+                .await?
+                .unwrap_or(current.amount);
 
-    fn get_cost_per_action(&self, ad: &AdData) -> f64 {
-        ad.cost_per_action_type
-            .iter()
-            .find(|action| action.action_type == "offsite_conversion.fb_pixel_custom")
-            .and_then(|action| action.value.parse::<f64>().ok())
-            .unwrap_or(0.0)
+            let mut budget = (current.amount * (1.0 + rule.increase_pct / 100.0))
+                .min(day_start_budget * (1.0 + rule.max_daily_increase_pct / 100.0));
+            if let Some(ceiling) = rule.max_daily_budget {
+                budget = budget.min(ceiling);
+            }
+            let budget = Money::new(budget, &current.currency).floor_to_graph();
+            if budget.amount <= current.amount {
+                continue;
+            }
+
+            window_costs.push(costs.iter().flatten().sum::<f64>() / costs.len() as f64);
+            raises.push((ad_set, budget));
+        }
+        if raises.is_empty() {
+            return Ok(());
+        }
+
+        // Every raise Facebook applied is recorded, so the daily cap holds even
+        // when an account-wide error stopped the rest
+        let report = fb_api.update_daily_budgets(&raises).await;
+        for (update, cost_per_action) in report.updates.into_iter().zip(window_costs) {
+            match &update.outcome {
+                StatusUpdateOutcome::Updated => {
+                    let change = BudgetChange {
+                        adset_id: update.adset_id.clone(),
+                        adset_name: update.adset_name.clone(),
+                        old_budget: update.old_budget.clone(),
+                        new_budget: update.new_budget.clone(),
+                        cost_per_action,
+                    };
+                    self.db.record_budget_change(account.id, &change).await?;
+                    messages.push(format!(
+                        "📈 Raised daily budget of ad set {}: {} → {} (💰{} per action over the last {} days)",
+                        update.adset_name,
+                        update.old_budget,
+                        update.new_budget,
+                        Money::new(cost_per_action, &update.new_budget.currency),
+                        window_days
+                    ));
+                }
+                StatusUpdateOutcome::Failed(reason) => messages.push(format!(
+                    "⚠️ Failed to raise daily budget of ad set {} ({}): {}",
+                    update.adset_name, update.adset_id, reason
+                )),
+                StatusUpdateOutcome::AlreadyInState => {}
+            }
+        }
+
+        match report.error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
     }
 
-    pub async fn run(&self, facebook_accounts: Vec<FacebookAccount>) -> Result<(), Box<dyn Error>> {
+    /// Runs every active account on its own interval, as scheduled by
+    /// `Scheduler`, with up to `max_concurrent_runs` accounts in flight at once.
+    /// Accounts are re-read every `reload_secs` and the schedule follows them.
+    pub async fn run(self: Arc<Self>) -> Result<(), Box<dyn Error + Send + Sync>> {
+      let mut facebook_accounts = self.db.get_facebook_accounts().await?;
+      let mut scheduler = Scheduler::new(self.schedule);
+      scheduler.sync(&facebook_accounts, Utc::now());
+
+      let mut reload = time::interval(Duration::from_secs(self.schedule.reload_secs));
+      reload.set_missed_tick_behavior(MissedTickBehavior::Delay);
+      reload.tick().await; // The first tick completes immediately
+
+      let mut runs = JoinSet::new();
+      let mut in_flight: HashMap<task::Id, (i32, DateTime<Utc>)> = HashMap::new();
+
       loop {
-          for account in &facebook_accounts {
-              if let Err(e) = self.process_account(account).await {
-                  eprintln!("Error processing account {}: {}", account.account_id, e);
-                  let telegram = TelegramNotifier::new(account.telegram_config.clone());
-                  telegram.send_message(&format!(
-                      "🚨 Error in account {}: {}", 
-                      account.account_id, 
-                      e
-                  )).await?;
+          for id in scheduler.due(Utc::now()) {
+              if in_flight.len() >= self.schedule.max_concurrent_runs {
+                  break;
+              }
+              // An account is never run twice at the same time
+              if in_flight.values().any(|(running, _)| *running == id) {
+                  continue;
+              }
+              let Some(account) = facebook_accounts.iter().find(|account| account.id == id).cloned() else {
+                  continue;
+              };
+
+              let started_at = Utc::now();
+              let worker = self.clone();
+              let handle = runs.spawn(async move {
+                  worker.run_account(&account).await;
+                  if let Err(e) = worker.db.record_run(account.id, started_at).await {
+                      eprintln!("Failed to record run for account {}: {}", account.account_id, e);
+                  }
+              });
+              in_flight.insert(handle.id(), (id, started_at));
+          }
+
+          // At the limit only a finished run can free a slot
+          let running: Vec<i32> = in_flight.values().map(|(id, _)| *id).collect();
+          let wait = if in_flight.len() < self.schedule.max_concurrent_runs {
+              scheduler.time_until_next(Utc::now(), &running)
+          } else {
+              None
+          };
+
+          tokio::select! {
+              Some(finished) = runs.join_next_with_id() => {
+                  let task_id = match finished {
+                      Ok((task_id, ())) => task_id,
+                      Err(e) => {
+                          eprintln!("Account run aborted: {}", e);
+                          e.id()
+                      }
+                  };
+                  if let Some((id, started_at)) = in_flight.remove(&task_id) {
+                      scheduler.completed(id, started_at, Utc::now());
+                  }
+              }
+              _ = time::sleep(wait.unwrap_or_default()), if wait.is_some() => {}
+              _ = reload.tick() => {
+                  match self.db.get_facebook_accounts().await {
+                      Ok(accounts) => {
+                          scheduler.sync(&accounts, Utc::now());
+                          facebook_accounts = accounts;
+                      }
+                      Err(e) => eprintln!("Failed to reload accounts, keeping the current schedule: {}", e),
+                  }
               }
           }
-  
-          time::sleep(WORKER_INTERVAL).await;
       }
   }
-} 
\ No newline at end of file
+
+  /// Processes one account within the run timeout, recording and reporting
+  /// a failed run.
+  async fn run_account(&self, account: &FacebookAccount) {
+      let timeout = Duration::from_secs(self.schedule.run_timeout_secs);
+      let result = match time::timeout(timeout, self.process_account(account, Instant::now() + timeout)).await {
+          Ok(result) => result,
+          // Throttling that outlasted the run is not worth an error message
+          Err(_) if self.rate_limiter.is_blocked(&account.account_id) => Err(FacebookApiError::RateLimited(
+              GraphError::local_throttle(format!("run timed out after {:?} while requests were paused", timeout)),
+          ).into()),
+          Err(_) => Err(format!("run timed out after {:?}", timeout).into()),
+      };
+
+      if let Err(e) = result {
+          if let Some(e) = e.downcast_ref::<FacebookApiError>().filter(|e| e.is_auth_error()) {
+              let info = TokenInfo::invalid(redact_account_secrets(account, &e.to_string()));
+              if let Err(e) = self.db.update_token_status(account.id, &info).await {
+                  eprintln!("Failed to record token status for account {}: {}", account.account_id, e);
+              }
+          }
+          eprintln!(
+              "Error processing account {}: {}",
+              account.account_id,
+              redact_account_secrets(account, &e.to_string())
+          );
+          if let Some(message) = error_notification(&account.account_id, e.as_ref()) {
+              let telegram = TelegramNotifier::new(account.telegram_config.clone(), self.retry);
+              let message = redact_account_secrets(account, &message);
+              if let Err(e) = telegram.send_message(&message).await {
+                  eprintln!(
+                      "Failed to notify account {}: {}",
+                      account.account_id,
+                      redact_account_secrets(account, &e.to_string())
+                  );
+              }
+          }
+      }
+  }
+}
+
+fn balance_message(account_id: &str, balance: &AccountBalance) -> String {
+    let mut lines = vec![match balance.remaining_funds() {
+        Some(remaining) => format!("💰 Account balance {}: {} remaining", account_id, remaining),
+        None => format!("💰 Account balance {}: no spend limit", account_id),
+    }];
+
+    if let Some(cap) = &balance.spend_cap {
+        lines.push(format!("📈 Spent {} of {} spend cap", balance.amount_spent, cap));
+    }
+    if !balance.is_prepaid {
+        lines.push(format!("🧾 Unbilled: {}", balance.balance));
+    }
+    if let Some(source) = &balance.funding_source {
+        lines.push(format!("💳 Funding: {}", source));
+    }
+
+    lines.join("\n")
+}
+
+fn status_message(object: &dyn AdObject, paused: bool, cost_per_action: Option<f64>, currency: &str) -> String {
+    let cost = match cost_per_action {
+        Some(cost) => format!("💰{}", Money::new(cost, currency)),
+        None => "⏳ not enough data".to_string(),
+    };
+    if paused {
+        format!("❌ {} paused: {}:{}", object.level().label(), object.name(), cost)
+    } else {
+        format!("🟢 {} active: {}:{}", object.level().label(), object.name(), cost)
+    }
+}
+
+fn status_update_message(update: &StatusUpdate) -> String {
+    let level = update.level.label();
+    match &update.outcome {
+        StatusUpdateOutcome::Updated => {
+            format!("🧠 Updated {} status: {} to {}", level.to_lowercase(), update.name, update.status)
+        }
+        StatusUpdateOutcome::AlreadyInState => {
+            format!("ℹ️ {} already {}: {}", level, update.status, update.name)
+        }
+        StatusUpdateOutcome::Failed(reason) => format!(
+            "⚠️ Failed to update {} status: {} ({}) to {}: {}",
+            level.to_lowercase(), update.name, update.id, update.status, reason
+        ),
+    }
+}
+
+/// Last line of defence before an error is logged or posted to a shared chat.
+fn redact_account_secrets(account: &FacebookAccount, text: &str) -> String {
+    account
+        .telegram_config
+        .bot_token
+        .redact(&account.access_token.redact(text))
+}
+
+/// Builds the Telegram message for a failed account cycle, or `None` when the
+/// failure is expected to clear up on its own and is only worth logging.
+fn error_notification(account_id: &str, error: &(dyn Error + 'static)) -> Option<String> {
+    let message = match error.downcast_ref::<FacebookApiError>() {
+        Some(e) if e.is_auth_error() => format!(
+            "🔑 Access token for account {} is no longer valid, please reconnect it: {}",
+            account_id, e
+        ),
+        Some(e @ FacebookApiError::PermissionDenied(_)) => format!(
+            "🚫 Missing permission for account {}: {}",
+            account_id, e
+        ),
+        Some(FacebookApiError::InsufficientFunds) => format!(
+            "💸 Account {} has an unsettled balance, ads are not delivering",
+            account_id
+        ),
+        Some(FacebookApiError::RateLimited(_) | FacebookApiError::TemporarilyUnavailable(_)) => {
+            return None;
+        }
+        _ => format!("🚨 Error in account {}: {}", account_id, error),
+    };
+    Some(message)
+}
+
+/// The status an ad, ad set or campaign should be changed to, if any.
+/// Nothing changes while there is too little data to judge it.
+fn decide_status(object: &dyn AdObject, thresholds: &AdThresholds, cost_per_action: Option<f64>) -> Option<&'static str> {
+    let cost_per_action = cost_per_action?;
+    if should_close(object, thresholds, cost_per_action) {
+        Some("PAUSED")
+    } else if should_open(object, thresholds, cost_per_action) {
+        Some("ACTIVE")
+    } else {
+        None
+    }
+}
+
+fn should_close(object: &dyn AdObject, thresholds: &AdThresholds, cost_per_action: f64) -> bool {
+    object.status() == "ACTIVE"
+        && object.effective_status() == "ACTIVE"
+        && cost_per_action > thresholds.max_cost_per_action
+}
+
+// PAUSED effective status means the object itself is paused while its parents are active
+fn should_open(object: &dyn AdObject, thresholds: &AdThresholds, cost_per_action: f64) -> bool {
+    object.status() == "PAUSED"
+        && object.effective_status() == "PAUSED"
+        && cost_per_action < thresholds.resume_cost_per_action
+}
+
+/// True while an object whose status was last changed at `changed_at` is
+/// still inside its cooldown and must be left alone.
+fn in_cooldown(changed_at: DateTime<Utc>, thresholds: &AdThresholds, now: DateTime<Utc>) -> bool {
+    now - changed_at < ChronoDuration::hours(thresholds.cooldown_hours)
+}
+
+/// Cost per action the rule judges an object by, or `None` while it lacks
+/// the minimum data. With fewer than `min_conversions` conversions only
+/// spend decides: the cost is taken as spend over `min_conversions`, and
+/// only when that already exceeds the threshold.
+fn judged_cost(object: &dyn AdObject, thresholds: &AdThresholds, action_types: &[String]) -> Option<f64> {
+    let guards = &thresholds.guards;
+    let performance = object.performance();
+    let insights = performance.insights.as_ref()?;
+
+    let old_enough = object.created_time().is_none_or(|created| {
+        Utc::now() - created >= ChronoDuration::hours(guards.min_age_hours)
+    });
+    if !old_enough || insights.spend < guards.min_spend || insights.impressions < guards.min_impressions {
+        return None;
+    }
+
+    let min_conversions = guards.min_conversions.max(1) as f64;
+    if performance.action_count(action_types) >= min_conversions {
+        return performance.cost_per_action(action_types);
+    }
+
+    Some(insights.spend / min_conversions).filter(|cost| *cost > thresholds.max_cost_per_action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::facebook::{ActionStat, AdInsights, Performance};
+
+    const LEAD: &str = "lead";
+
+    fn ad(age_hours: i64, spend: f64, impressions: i64, conversions: u32) -> AdData {
+        AdData {
+            id: "ad1".to_string(),
+            name: "Ad 1".to_string(),
+            status: "ACTIVE".to_string(),
+            effective_status: "ACTIVE".to_string(),
+            created_time: Some(Utc::now() - ChronoDuration::hours(age_hours)),
+            adset_id: "a1".to_string(),
+            campaign_id: "c1".to_string(),
+            performance: Performance {
+                insights: Some(AdInsights {
+                    impressions,
+                    reach: impressions,
+                    clicks: 0,
+                    spend,
+                    date_start: None,
+                    date_stop: None,
+                }),
+                actions: vec![ActionStat {
+                    action_type: LEAD.to_string(),
+                    value: conversions.to_string(),
+                }],
+                cost_per_action_type: Vec::new(),
+            },
+        }
+    }
+
+    fn ad_thresholds(max_cost_per_action: f64, resume_cost_per_action: f64, guards: DataGuards) -> AdThresholds {
+        AdThresholds {
+            max_cost_per_action,
+            resume_cost_per_action,
+            cooldown_hours: THRESHOLD_COOLDOWN_HOURS,
+            control_level: ControlLevel::Ad,
+            guards,
+        }
+    }
+
+    fn guarded() -> AdThresholds {
+        ad_thresholds(100.0, 100.0, DataGuards {
+            min_spend: 50.0,
+            min_impressions: 1000,
+            min_age_hours: 24,
+            min_conversions: 3,
+        })
+    }
+
+    fn lead() -> Vec<String> {
+        vec![LEAD.to_string()]
+    }
+
+    #[test]
+    fn judged_cost_waits_for_minimum_data() {
+        let thresholds = guarded();
+
+        assert_eq!(judged_cost(&ad(12, 500.0, 5000, 0), &thresholds, &lead()), None); // Too young
+        assert_eq!(judged_cost(&ad(48, 40.0, 5000, 0), &thresholds, &lead()), None); // Too little spend
+        assert_eq!(judged_cost(&ad(48, 500.0, 500, 0), &thresholds, &lead()), None); // Too few impressions
+    }
+
+    #[test]
+    fn judged_cost_without_enough_conversions_only_counts_towards_pausing() {
+        let thresholds = guarded();
+
+        // 450 / 3 = 150 is above the maximum, so the spend alone justifies a pause
+        assert_eq!(judged_cost(&ad(48, 450.0, 5000, 0), &thresholds, &lead()), Some(150.0));
+        assert_eq!(judged_cost(&ad(48, 450.0, 5000, 2), &thresholds, &lead()), Some(150.0));
+        // 240 / 3 = 80 is below it, and too few conversions never resume anything
+        assert_eq!(judged_cost(&ad(48, 240.0, 5000, 0), &thresholds, &lead()), None);
+        assert_eq!(judged_cost(&ad(48, 240.0, 5000, 2), &thresholds, &lead()), None);
+    }
+
+    #[test]
+    fn judged_cost_with_enough_conversions_is_the_real_cost_per_action() {
+        let thresholds = guarded();
+
+        assert_eq!(judged_cost(&ad(48, 240.0, 5000, 4), &thresholds, &lead()), Some(60.0));
+        assert_eq!(judged_cost(&ad(48, 600.0, 5000, 3), &thresholds, &lead()), Some(200.0));
+    }
+
+    fn with_status(status: &str, effective_status: &str) -> AdData {
+        let mut ad = ad(48, 500.0, 5000, 5);
+        ad.status = status.to_string();
+        ad.effective_status = effective_status.to_string();
+        ad
+    }
+
+    fn band() -> AdThresholds {
+        ad_thresholds(120.0, 90.0, DataGuards::default())
+    }
+
+    #[test]
+    fn cost_inside_the_band_changes_nothing() {
+        let active = with_status("ACTIVE", "ACTIVE");
+        let paused = with_status("PAUSED", "PAUSED");
+
+        for cost in [90.0, 100.0, 120.0] {
+            assert_eq!(decide_status(&active, &band(), Some(cost)), None);
+            assert_eq!(decide_status(&paused, &band(), Some(cost)), None);
+        }
+    }
+
+    #[test]
+    fn cost_above_the_maximum_pauses_an_active_object() {
+        assert_eq!(decide_status(&with_status("ACTIVE", "ACTIVE"), &band(), Some(121.0)), Some("PAUSED"));
+        // Already paused, or not delivering because of a parent
+        assert_eq!(decide_status(&with_status("PAUSED", "PAUSED"), &band(), Some(121.0)), None);
+        assert_eq!(decide_status(&with_status("ACTIVE", "ADSET_PAUSED"), &band(), Some(121.0)), None);
+    }
+
+    #[test]
+    fn cost_below_resume_threshold_resumes_only_objects_paused_themselves() {
+        assert_eq!(decide_status(&with_status("PAUSED", "PAUSED"), &band(), Some(89.0)), Some("ACTIVE"));
+        // Paused by its ad set or campaign: resuming the ad would not deliver
+        assert_eq!(decide_status(&with_status("PAUSED", "ADSET_PAUSED"), &band(), Some(89.0)), None);
+        assert_eq!(decide_status(&with_status("ACTIVE", "ACTIVE"), &band(), Some(89.0)), None);
+    }
+
+    #[test]
+    fn unknown_cost_changes_nothing() {
+        assert_eq!(decide_status(&with_status("ACTIVE", "ACTIVE"), &band(), None), None);
+        assert_eq!(decide_status(&with_status("PAUSED", "PAUSED"), &band(), None), None);
+    }
+
+    #[test]
+    fn objects_changed_within_cooldown_are_skipped() {
+        let now = Utc::now();
+        let thresholds = AdThresholds { cooldown_hours: 24, ..band() };
+
+        assert!(in_cooldown(now - ChronoDuration::hours(2), &thresholds, now));
+        assert!(in_cooldown(now - ChronoDuration::hours(23), &thresholds, now));
+        assert!(!in_cooldown(now - ChronoDuration::hours(24), &thresholds, now));
+        assert!(!in_cooldown(now - ChronoDuration::hours(2), &AdThresholds { cooldown_hours: 0, ..band() }, now));
+    }
+
+    #[test]
+    fn judged_cost_without_insights_is_unknown() {
+        let mut ad = ad(48, 500.0, 5000, 5);
+        ad.performance.insights = None;
+
+        assert_eq!(judged_cost(&ad, &guarded(), &lead()), None);
+    }
+
+    fn rule(account_id: Option<i32>, campaign_id: Option<&str>, adset_id: Option<&str>) -> ThresholdRule {
+        ThresholdRule {
+            account_id,
+            campaign_id: campaign_id.map(str::to_string),
+            adset_id: adset_id.map(str::to_string),
+            max_cost_per_action: None,
+            resume_cost_per_action: None,
+            cooldown_hours: None,
+            control_level: None,
+            min_spend: None,
+            min_impressions: None,
+            min_age_hours: None,
+            min_conversions: None,
+        }
+    }
+
+    fn thresholds() -> AdMetricsThresholds {
+        AdMetricsThresholds {
+            rules: vec![
+                ThresholdRule {
+                    max_cost_per_action: Some(100.0),
+                    cooldown_hours: Some(12),
+                    min_impressions: Some(1000),
+                    ..rule(None, None, None)
+                },
+                ThresholdRule {
+                    max_cost_per_action: Some(80.0),
+                    control_level: Some(ControlLevel::AdSet),
+                    ..rule(Some(1), None, None)
+                },
+                ThresholdRule {
+                    resume_cost_per_action: Some(50.0),
+                    min_spend: Some(10.0),
+                    ..rule(Some(1), Some("c1"), None)
+                },
+                ThresholdRule {
+                    max_cost_per_action: Some(60.0),
+                    ..rule(Some(1), Some("c1"), Some("a1"))
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn ad_set_inherits_from_campaign_account_and_global() {
+        let resolved = thresholds().resolve("c1", Some("a1")).unwrap();
+
+        assert_eq!(resolved.max_cost_per_action, 60.0); // Ad set
+        assert_eq!(resolved.resume_cost_per_action, 50.0); // Campaign
+        assert_eq!(resolved.guards.min_spend, 10.0); // Campaign
+        assert_eq!(resolved.control_level, ControlLevel::AdSet); // Account
+        assert_eq!(resolved.cooldown_hours, 12); // Global
+        assert_eq!(resolved.guards.min_impressions, 1000); // Global
+        assert_eq!(resolved.guards.min_conversions, 1); // Default
+    }
+
+    #[test]
+    fn ad_set_rule_does_not_apply_to_its_campaign() {
+        let resolved = thresholds().resolve("c1", None).unwrap();
+
+        assert_eq!(resolved.max_cost_per_action, 80.0);
+        assert_eq!(resolved.resume_cost_per_action, 50.0);
+    }
+
+    #[test]
+    fn other_campaigns_fall_back_to_account_and_defaults() {
+        let resolved = thresholds().resolve("c2", Some("a9")).unwrap();
+
+        assert_eq!(resolved.max_cost_per_action, 80.0);
+        assert_eq!(resolved.resume_cost_per_action, 80.0);
+        assert_eq!(resolved.guards.min_spend, 0.0);
+    }
+
+    #[test]
+    fn resume_threshold_is_capped_at_pause_threshold() {
+        let mut thresholds = thresholds();
+        thresholds.rules.push(ThresholdRule {
+            max_cost_per_action: Some(40.0),
+            ..rule(Some(1), Some("c1"), Some("a2"))
+        });
+        let resolved = thresholds.resolve("c1", Some("a2")).unwrap();
+
+        assert_eq!(resolved.max_cost_per_action, 40.0);
+        assert_eq!(resolved.resume_cost_per_action, 40.0);
+    }
+
+    #[test]
+    fn no_maximum_means_no_thresholds() {
+        let thresholds = AdMetricsThresholds {
+            rules: vec![ThresholdRule {
+                cooldown_hours: Some(6),
+                ..rule(Some(1), None, None)
+            }],
+        };
+
+        assert!(thresholds.resolve("c1", Some("a1")).is_none());
+        assert!(AdMetricsThresholds { rules: Vec::new() }.resolve("c1", None).is_none());
+    }
+}